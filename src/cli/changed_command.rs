@@ -2,7 +2,11 @@ use std::collections::HashSet;
 
 use tabled::{Table, Tabled};
 
-use crate::{config::ValidPath, git, workspace::Workspace};
+use crate::{
+    config::ValidPath,
+    git,
+    workspace::{DevDependencies, Workspace},
+};
 
 #[derive(clap::Parser)]
 pub struct ChangedOpts {
@@ -17,6 +21,11 @@ pub struct ChangedOpts {
     /// Defaults to showing a table if running interactively, plain otherwise.
     #[clap(long, default_value_t = Format::Auto)]
     pub format: Format,
+
+    /// Don't count projects as affected when they only depend on a changed
+    /// project as a dev dependency.
+    #[clap(long)]
+    pub exclude_dev: bool,
 }
 
 #[derive(Clone)]
@@ -46,10 +55,14 @@ pub fn run(workspace: Workspace, opts: ChangedOpts) -> miette::Result<()> {
         .collect::<HashSet<_>>();
 
     let graph = workspace.graph();
+    let dev = match opts.exclude_dev {
+        true => DevDependencies::Exclude,
+        false => DevDependencies::Include,
+    };
 
     let projects_affected = projects_changed
         .into_iter()
-        .flat_map(|p| graph.walk_project_dependents(p.project_ref()))
+        .flat_map(|p| graph.walk_project_dependents(p.project_ref(), dev))
         .collect::<HashSet<_>>();
 
     // TODO: Probably topsort the output.
@@ -0,0 +1,67 @@
+use camino::Utf8PathBuf;
+
+use crate::{
+    config::{check_dependencies, ValidConfig},
+    diagnostics::DynDiagnostic,
+};
+
+#[derive(clap::Parser)]
+pub enum CheckCommand {
+    /// Checks that each projects dependencies match its Cargo.toml, package.json or go.mod
+    Deps(DepsOpts),
+}
+
+#[derive(clap::Parser)]
+pub struct DepsOpts {
+    /// Rewrite project files so that their dependencies match their manifests
+    #[clap(long)]
+    fix: bool,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum CheckError {
+    #[error("Some projects dependencies don't match their manifests")]
+    DependencyDrift {
+        #[related]
+        differences: Vec<DynDiagnostic>,
+    },
+    #[error("Couldn't write {0}")]
+    WriteError(Utf8PathBuf, #[source] std::io::Error),
+}
+
+pub fn run(config: ValidConfig, command: CheckCommand) -> miette::Result<()> {
+    match command {
+        CheckCommand::Deps(opts) => check_deps(config, opts),
+    }
+}
+
+fn check_deps(config: ValidConfig, opts: DepsOpts) -> miette::Result<()> {
+    let drift = check_dependencies(&config)?;
+
+    if opts.fix {
+        for project in drift {
+            let path = project
+                .project_file
+                .project_root
+                .full_path()
+                .join("project.kdl");
+            std::fs::write(&path, project.fixed_source())
+                .map_err(|e| CheckError::WriteError(path.clone(), e))?;
+            println!(
+                "Updated dependencies in {}",
+                project.project_file.source.filename()
+            );
+        }
+        return Ok(());
+    }
+
+    let differences = drift
+        .iter()
+        .flat_map(|project| project.diagnostics())
+        .collect::<Vec<_>>();
+    if !differences.is_empty() {
+        return Err(CheckError::DependencyDrift { differences }.into());
+    }
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
-use std::str::FromStr;
+use std::{collections::HashSet, str::FromStr};
 
 use camino::Utf8PathBuf;
 use chumsky::prelude::*;
 
+use crate::workspace::{DevDependencies, ProjectInfo, Workspace};
+
 #[derive(Clone, Debug)]
 pub struct ProjectFilter {
     pub specs: Vec<FilterSpec>,
@@ -63,6 +65,54 @@ impl std::fmt::Display for FilterSpec {
     }
 }
 
+#[tracing::instrument(skip(workspace))]
+pub fn filter_projects(
+    workspace: &Workspace,
+    filter: Option<ProjectFilter>,
+    dev: DevDependencies,
+) -> HashSet<&ProjectInfo> {
+    let specs = filter.map(|pf| pf.specs).unwrap_or_default();
+    if specs.is_empty() {
+        // TODO: If we're being run from within a project automatically filter to
+        // that project.
+        return workspace.projects().collect();
+    }
+
+    let mut cumulative_selection = HashSet::new();
+    for spec in specs {
+        let mut current_selection = HashSet::new();
+        // First determine which projects match this spec.
+        for project in workspace.projects() {
+            let matches = match &spec.matcher {
+                Matcher::Path(path) => project.root.as_subpath() == path,
+                Matcher::Name(name) => project.name == *name,
+            };
+            if matches {
+                current_selection.insert(project);
+            }
+        }
+
+        // Then pull in any deps from the graph as dictated by the spec.
+        let mut related = Vec::new();
+        for project in &current_selection {
+            if spec.include_dependencies {
+                related.extend(project.dependencies::<Vec<_>>(workspace, dev));
+            }
+            if spec.include_dependents {
+                related.extend(
+                    workspace
+                        .graph()
+                        .walk_project_dependents(project.project_ref(), dev),
+                );
+            }
+        }
+        current_selection.extend(related.iter().map(|project| project.lookup(workspace)));
+        cumulative_selection.extend(current_selection);
+    }
+
+    cumulative_selection
+}
+
 #[derive(Debug, thiserror::Error, miette::Diagnostic)]
 #[error("Couldn't parse a package filter: {errors}")]
 pub struct PackageFilterParseErrors {
@@ -98,15 +148,20 @@ fn parser() -> impl chumsky::Parser<char, ProjectFilter, Error = Simple<char>> {
     let package_name = filter(is_package_char)
         .map(Some)
         .chain::<char, Vec<_>, _>(filter(is_package_char).repeated())
-        .collect::<String>()
-        .map(|name| FilterSpec {
-            include_dependents: false,
-            include_dependencies: false,
+        .collect::<String>();
+
+    // A leading ... includes the dependents of a package, a trailing one its dependencies
+    let spec = just("...")
+        .or_not()
+        .then(package_name)
+        .then(just("...").or_not())
+        .map(|((dependents, name), dependencies)| FilterSpec {
+            include_dependents: dependents.is_some(),
+            include_dependencies: dependencies.is_some(),
             matcher: Matcher::Name(name),
         });
 
-    package_name
-        .separated_by(just(','))
+    spec.separated_by(just(','))
         .allow_trailing()
         .at_least(1)
         .map(|specs| ProjectFilter { specs })
@@ -115,12 +170,47 @@ fn parser() -> impl chumsky::Parser<char, ProjectFilter, Error = Simple<char>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::workspace::tests::a_workspace;
     use rstest::rstest;
 
     #[rstest]
     #[case::package_name("a-library")]
     #[case::several_package_names("a-library,a-service")]
+    #[case::dependents("...a-library")]
+    #[case::dependencies("a-service...")]
+    #[case::dependencies_and_dependents("...a-library...,a-service")]
     fn test_parsing_package_name(#[case] input: &str) {
         assert_eq!(input.parse::<ProjectFilter>().unwrap().to_string(), input);
     }
+
+    #[test]
+    fn test_filtering_projects() {
+        let workspace = a_workspace();
+
+        let names = |filter: &str, dev: DevDependencies| {
+            let mut names = filter_projects(&workspace, Some(filter.parse().unwrap()), dev)
+                .into_iter()
+                .map(|project| project.name.as_str())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names("a-service...", DevDependencies::Include),
+            vec!["a-lib", "a-service", "test-fixtures"]
+        );
+        assert_eq!(
+            names("a-service...", DevDependencies::Exclude),
+            vec!["a-lib", "a-service"]
+        );
+        assert_eq!(
+            names("...test-fixtures", DevDependencies::Include),
+            vec!["a-service", "test-fixtures"]
+        );
+        assert_eq!(
+            names("...test-fixtures", DevDependencies::Exclude),
+            vec!["test-fixtures"]
+        );
+    }
 }
@@ -1,9 +1,13 @@
 use camino::Utf8PathBuf;
 use clap::Parser;
 
-use crate::{config::load_config_from_path, workspace::Workspace};
+use crate::{
+    config::{load_config_from_path, ValidConfig},
+    workspace::Workspace,
+};
 
 mod changed_command;
+mod check_command;
 mod filters;
 mod git_commands;
 mod graph_command;
@@ -33,32 +37,51 @@ pub enum Command {
     /// Subcommands for manipulating a sparse-checkout git repository
     #[clap(subcommand)]
     Git(git_commands::GitCommand),
+    /// Subcommands for checking the workspace configuration
+    #[clap(subcommand)]
+    Check(check_command::CheckCommand),
 }
 
 pub fn run() -> miette::Result<()> {
     tracing_subscriber::fmt::init();
 
     let opts = Cli::parse();
-    let workspace = load_workspace()?;
+    let config = load_config()?;
+
+    // Checks work on the config files themselves, so don't need a workspace
+    let command = match opts.command {
+        Command::Check(command_opts) => return check_command::run(config, command_opts),
+        command => command,
+    };
+    let workspace = load_workspace(config)?;
 
-    match opts.command {
+    match command {
         Command::Changed(command_opts) => changed_command::run(workspace, command_opts),
         Command::Run(command_opts) => run_command::run(workspace, command_opts),
         Command::Projects(command_opts) => projects_command::run(workspace, command_opts),
         Command::Tasks(command_opts) => tasks_command::run(workspace, command_opts),
         Command::Graph(command_opts) => graph_command::run(workspace, command_opts),
         Command::Git(command_opts) => git_commands::run(workspace, command_opts),
+        Command::Check(_) => unreachable!("check commands should have been run already"),
     }
 }
 
-fn load_workspace() -> Result<Workspace, miette::Report> {
-    let config = load_config_from_path(
+fn load_config() -> Result<ValidConfig, miette::Report> {
+    let mut config = load_config_from_path(
         Utf8PathBuf::try_from(
             std::env::current_dir().expect("couldn't determine current directory"),
         )
         .expect("the current directory to be a utf8 path"),
     )?;
 
+    for warning in std::mem::take(&mut config.warnings) {
+        eprintln!("{:?}", miette::Report::new(warning));
+    }
+
+    Ok(config)
+}
+
+fn load_workspace(config: ValidConfig) -> Result<Workspace, miette::Report> {
     let mut workspace = Workspace::new(config.workspace_file);
 
     workspace.add_projects(config.project_files)?;
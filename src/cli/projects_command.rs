@@ -26,6 +26,7 @@ pub fn run(workspace: Workspace, opts: ProjectsOpts) -> miette::Result<()> {
     let outputs = workspace.projects().map(|p| Output {
         name: p.name.clone(),
         path: p.root.clone(),
+        tags: p.tags.clone(),
     });
 
     match opts.format.actual_format() {
@@ -55,6 +56,12 @@ pub fn run(workspace: Workspace, opts: ProjectsOpts) -> miette::Result<()> {
 pub struct Output {
     name: String,
     path: ValidPath,
+    #[tabled(display_with = "display_tags")]
+    tags: Vec<String>,
+}
+
+fn display_tags(tags: &[String]) -> String {
+    tags.join(", ")
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -9,11 +9,14 @@ use tokio::runtime::Runtime;
 
 use crate::{
     hashing::{HashError, HashRegistry},
-    workspace::{ProjectInfo, TaskRef, Workspace},
+    workspace::{DevDependencies, ProjectInfo, TaskInfo, TaskRef, Workspace},
 };
 
-use self::{output::build_command_outputs, runner::TaskRunner};
-use super::filters::ProjectFilter;
+use self::{
+    output::build_command_outputs,
+    runner::{RunSettings, TaskRunner},
+};
+use super::filters::{filter_projects, ProjectFilter};
 
 mod child_ext;
 mod output;
@@ -22,6 +25,8 @@ mod runner;
 #[derive(clap::Parser)]
 pub struct RunOpts {
     /// The task or tasks to be run.
+    ///
+    /// Tasks defined in the workspace.kdl can be run with `//::<task>`.
     #[clap(value_parser)]
     pub tasks: Vec<String>,
 
@@ -37,6 +42,27 @@ pub struct RunOpts {
     /// Defaults to changes since the last run of a task (or will once that's implemented)
     #[clap(long)]
     pub since: Option<String>,
+
+    /// Runs tasks with a hermetic environment.
+    ///
+    /// Tasks will only see the environment variables allowed by `env_passthrough` in
+    /// the workspace.kdl, the variables they declare inputs on, and any they set
+    /// themselves.
+    #[clap(long)]
+    pub hermetic_env: bool,
+
+    /// Don't follow dev dependencies when a filter includes the dependencies or
+    /// dependents of a project.
+    #[clap(long)]
+    pub exclude_dev: bool,
+
+    /// Extra arguments to pass through to the tasks named on the command line.
+    ///
+    /// These are substituted wherever `{args}` appears in a tasks commands, or appended
+    /// to the final command if there's no such placeholder.  Tasks that are only being
+    /// run because they're required by another task will not receive these.
+    #[clap(last = true)]
+    pub args: Vec<String>,
 }
 
 pub fn run(workspace: Workspace, opts: RunOpts) -> miette::Result<()> {
@@ -50,6 +76,10 @@ pub fn run(workspace: Workspace, opts: RunOpts) -> miette::Result<()> {
                 &workspace.projects_globset(),
             )
         }),
+        match opts.exclude_dev {
+            true => DevDependencies::Exclude,
+            false => DevDependencies::Include,
+        },
     );
     let tasks = find_tasks(&workspace, &target_projects, opts.tasks);
 
@@ -74,6 +104,12 @@ pub fn run(workspace: Workspace, opts: RunOpts) -> miette::Result<()> {
                 .map(|task| task.task_ref.lookup(&workspace))
                 .collect::<Vec<_>>(),
         );
+        let extra_args = tasks
+            .iter()
+            .filter(|task| task.requested)
+            .map(|task| (task.task_ref.clone(), opts.args.clone()))
+            .collect::<HashMap<_, _>>();
+
         let mut waiting = tasks
             .iter()
             .map(|task| (task.task_ref.clone(), task.deps.len()))
@@ -94,7 +130,17 @@ pub fn run(workspace: Workspace, opts: RunOpts) -> miette::Result<()> {
             }
         }
 
-        let mut runner = TaskRunner::new(&workspace, opts.since.clone(), outputs, &hash_registry);
+        let mut runner = TaskRunner::new(
+            &workspace,
+            RunSettings {
+                since: opts.since.clone(),
+                hermetic_env: opts.hermetic_env,
+                env_passthrough: Arc::new(workspace.env_passthrough_globset()),
+            },
+            outputs,
+            extra_args,
+            &hash_registry,
+        );
 
         for task in ready.drain(0..).rev() {
             tracing::debug!(%task, "Task has no dependencies, adding to ready list");
@@ -170,46 +216,16 @@ fn infer_filter(workspace_root: &Utf8Path, globset: &GlobSet) -> Option<ProjectF
     None
 }
 
-#[tracing::instrument(skip(workspace))]
-fn filter_projects(workspace: &Workspace, filter: Option<ProjectFilter>) -> HashSet<&ProjectInfo> {
-    let specs = filter.map(|pf| pf.specs).unwrap_or_default();
-    if specs.is_empty() {
-        // TODO: If we're being run from within a project automatically filter to
-        // that project.
-        return workspace.projects().collect();
-    }
-
-    let mut cumulative_selection = HashSet::new();
-    for spec in specs {
-        let mut current_selection = HashSet::new();
-        // First determine which projects match this spec.
-        for project in workspace.projects() {
-            let matches = match &spec.matcher {
-                super::filters::Matcher::Path(path) => project.root.as_subpath() == path,
-                super::filters::Matcher::Name(name) => project.name == *name,
-            };
-            if matches {
-                current_selection.insert(project);
-            }
-        }
-
-        // Then pull in any deps from the graph as dictated by the spec.
-        if spec.include_dependencies {
-            todo!()
-        }
-        if spec.include_dependents {
-            todo!()
-        }
-        cumulative_selection.extend(current_selection);
-    }
-
-    cumulative_selection
-}
+/// The prefix used to address tasks owned by the workspace root, e.g. `//::fmt`
+const ROOT_TASK_PREFIX: &str = "//::";
 
 #[derive(Debug, Clone)]
 struct TaskAndDeps {
     task_ref: TaskRef,
     deps: HashSet<TaskRef>,
+    /// Whether this task was named on the command line, rather than just being
+    /// required by one that was.
+    requested: bool,
 }
 
 #[tracing::instrument(
@@ -225,21 +241,38 @@ fn find_tasks<'a>(
 ) -> Vec<TaskAndDeps> {
     let graph = workspace.graph();
     let mut tasks = HashSet::new();
-    for project in target_projects {
-        for task_name in &task_list {
-            if let Some(task) = project.lookup_task(task_name, workspace) {
-                tasks.insert(task.task_ref());
-                let task_deps = graph.walk_task_dependencies(task.task_ref());
-                tracing::debug!(
-                    "{} depends on {}",
-                    task.task_ref(),
-                    task_deps
-                        .iter()
-                        .map(|d| d.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                );
-                tasks.extend(task_deps);
+    let mut requested = HashSet::new();
+    let mut add_task = |task: &TaskInfo| {
+        tasks.insert(task.task_ref());
+        requested.insert(task.task_ref());
+        let task_deps = graph.walk_task_dependencies(task.task_ref());
+        tracing::debug!(
+            "{} depends on {}",
+            task.task_ref(),
+            task_deps
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        tasks.extend(task_deps);
+    };
+    for task_name in &task_list {
+        // Tasks owned by the workspace root are only run when asked for explicitly
+        if let Some(task_name) = task_name.strip_prefix(ROOT_TASK_PREFIX) {
+            for task in workspace.root_tasks() {
+                if task.answers_to(task_name) {
+                    add_task(task);
+                }
+            }
+            continue;
+        }
+        // Asking for a matrix task by name runs all of its instances
+        for project in target_projects {
+            for task in project.tasks(workspace) {
+                if task.answers_to(task_name) {
+                    add_task(task);
+                }
             }
         }
     }
@@ -250,7 +283,18 @@ fn find_tasks<'a>(
         .into_iter()
         .filter(|task_ref| tasks.contains(task_ref))
         .map(|task_ref| TaskAndDeps {
-            deps: task_ref.direct_dependencies(workspace),
+            // Ordering constraints only apply when both tasks are being run
+            deps: task_ref
+                .direct_dependencies(workspace)
+                .into_iter()
+                .chain(
+                    task_ref
+                        .runs_after(workspace)
+                        .into_iter()
+                        .filter(|task| tasks.contains(task)),
+                )
+                .collect(),
+            requested: requested.contains(&task_ref),
             task_ref,
         })
         .collect()
@@ -264,6 +308,8 @@ enum TaskError {
     CommandError(std::io::Error),
     #[error("Error hashing inputs or outputs: {0}")]
     Hashing(#[from] HashError),
+    #[error("Error loading env file {0}: {1}")]
+    EnvFileError(Utf8PathBuf, dotenvy::Error),
     // #[error("Uncountered a path that wasn't UTF8: {0}")]
     // InvalidPathFound(#[from] camino::FromPathBufError),
     #[error("Error reading command output")]
@@ -281,3 +327,44 @@ enum TaskOutcome {
     Succesful,
     Failed(TaskError),
 }
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+    use crate::workspace::tests::a_workspace;
+
+    pub(super) fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_after_only_orders_tasks_being_run() {
+        let workspace = a_workspace();
+        let service = workspace.project_at_path("projects/a-service").unwrap();
+        let target_projects = maplit::hashset! { service };
+        let task_ref = |name: &str| service.lookup_task(name, &workspace).unwrap().task_ref();
+
+        let tasks = find_tasks(&workspace, &target_projects, strings(&["integration-test"]));
+        assert_eq!(tasks.len(), 1);
+        assert!(tasks[0].deps.is_empty());
+
+        let tasks = find_tasks(
+            &workspace,
+            &target_projects,
+            strings(&["integration-test", "migrate"]),
+        );
+        let deps = tasks
+            .into_iter()
+            .map(|task| (task.task_ref, task.deps))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            deps,
+            maplit::hashmap! {
+                task_ref("migrate") => HashSet::new(),
+                task_ref("integration-test") => maplit::hashset! { task_ref("migrate") },
+            }
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use std::{collections::HashMap, process::Stdio, sync::Arc};
+use std::{collections::HashMap, ffi::OsString, process::Stdio, sync::Arc};
 
 use futures::{stream::FuturesUnordered, StreamExt};
+use globset::GlobSet;
 use tokio::task::{block_in_place, JoinHandle};
 
 use crate::{
@@ -14,12 +15,23 @@ use super::{child_ext::ChildExt, output::CommandOutput, FinishedTask, TaskError,
 pub(super) struct TaskRunner {
     currently_running: FuturesUnordered<JoinHandle<FinishedTask>>,
     workspace: Arc<Workspace>,
-    since: Option<String>,
+    settings: RunSettings,
     outputs: HashMap<TaskRef, CommandOutput>,
+    extra_args: HashMap<TaskRef, Vec<String>>,
     hash_registry: Arc<HashRegistry>,
     outcomes: HashMap<TaskRef, SimplifiedOutcome>,
 }
 
+/// Settings that apply to every task in a run.
+#[derive(Clone, Debug)]
+pub(super) struct RunSettings {
+    pub since: Option<String>,
+    pub hermetic_env: bool,
+    /// The environment variables tasks can see when running hermetically.
+    pub env_passthrough: Arc<GlobSet>,
+}
+
+#[derive(Clone, Copy)]
 enum SimplifiedOutcome {
     Skipped,
     Succesful,
@@ -29,15 +41,17 @@ enum SimplifiedOutcome {
 impl TaskRunner {
     pub fn new(
         workspace: &Arc<Workspace>,
-        since: Option<String>,
+        settings: RunSettings,
         outputs: HashMap<TaskRef, CommandOutput>,
+        extra_args: HashMap<TaskRef, Vec<String>>,
         hash_registry: &Arc<HashRegistry>,
     ) -> TaskRunner {
         TaskRunner {
             currently_running: FuturesUnordered::new(),
             workspace: Arc::clone(workspace),
             outputs,
-            since,
+            extra_args,
+            settings,
             hash_registry: Arc::clone(hash_registry),
             outcomes: HashMap::new(),
         }
@@ -48,12 +62,13 @@ impl TaskRunner {
         tracing::debug!(task = %task_ref, "Starting task");
 
         let workspace = Arc::clone(&self.workspace);
-        let since = self.since.clone();
+        let settings = self.settings.clone();
         let hash_registry = Arc::clone(&self.hash_registry);
         let output = self
             .outputs
             .remove(&task_ref)
             .expect("a CommandOutput to exist for every task");
+        let extra_args = self.extra_args.remove(&task_ref).unwrap_or_default();
 
         let dependency_outcome = self.dependency_outcome(&task_ref);
 
@@ -62,7 +77,8 @@ impl TaskRunner {
                 task_ref.lookup(&workspace),
                 &workspace,
                 output,
-                since,
+                &extra_args,
+                &settings,
                 &hash_registry,
                 dependency_outcome,
             )
@@ -119,19 +135,38 @@ enum OutcomeSummary {
 
 #[tracing::instrument(
     fields(task = %task.task_ref())
-    skip(task, workspace, output, hash_registry)
+    skip(task, workspace, output, extra_args, settings, hash_registry)
 )]
 async fn run_task(
     task: &TaskInfo,
     workspace: &Workspace,
     output: CommandOutput,
-    since: Option<String>,
+    extra_args: &[String],
+    settings: &RunSettings,
     hash_registry: &HashRegistry,
     dependency_outcome: OutcomeSummary,
 ) -> Result<TaskOutcome, TaskError> {
+    if let Some(reason) = &task.disabled {
+        tracing::info!(task = %task.task_ref(), reason = %reason, "Skipping disabled task");
+        return Ok(TaskOutcome::Skipped);
+    }
+
+    // Tasks whose commands were all filtered out by `when` act just like aliases
+    if task.commands.is_empty() {
+        tracing::info!(task = %task.task_ref(), "Finished task without commands");
+        return Ok(alias_outcome(dependency_outcome));
+    }
+
     tracing::info!(task = %task.task_ref(), "Checking if task should run");
-    let (should_run, input_hash) =
-        block_in_place(|| should_task_run(task, workspace, since, hash_registry))?;
+    let (should_run, input_hash) = block_in_place(|| {
+        should_task_run(
+            task,
+            workspace,
+            extra_args,
+            settings.since.clone(),
+            hash_registry,
+        )
+    })?;
 
     if !should_run && dependency_outcome == OutcomeSummary::NoChange {
         tracing::info!(task = %task.task_ref(), "Skipping task");
@@ -140,8 +175,10 @@ async fn run_task(
 
     let mut output = output;
 
-    for command in &task.commands {
-        let mut args = command.split(' ');
+    let env_file_vars = load_env_files(task)?;
+
+    for command_line in command_lines(&task.commands, extra_args) {
+        let mut args = command_line.iter();
         let command = args
             .next()
             .expect("there to be some content in a tasks command");
@@ -149,9 +186,18 @@ async fn run_task(
 
         tracing::debug!(command=%command, args=?args, "Running command");
 
-        let mut child = tokio::process::Command::new(command)
+        let mut child = tokio::process::Command::new(command);
+        if settings.hermetic_env {
+            child
+                .env_clear()
+                .envs(passthrough_env(task, &settings.env_passthrough));
+        }
+
+        let mut child = child
             .args(args)
-            .current_dir(&task.project.lookup(workspace).root.full_path())
+            .current_dir(task.cwd.full_path())
+            .envs(env_file_vars.iter().cloned())
+            .envs(&task.env)
             .stderr(Stdio::piped())
             .stdout(Stdio::piped())
             .stdin(Stdio::null())
@@ -181,6 +227,83 @@ async fn run_task(
     Ok(TaskOutcome::Succesful)
 }
 
+/// The outcome of an alias, given the outcome of the tasks it requires.
+///
+/// Aliases don't do anything themselves, so they only count as having
+/// run if one of the tasks they require did.  They're never started if
+/// one of those tasks failed.
+fn alias_outcome(dependency_outcome: OutcomeSummary) -> TaskOutcome {
+    match dependency_outcome {
+        OutcomeSummary::SomeChange => TaskOutcome::Succesful,
+        OutcomeSummary::NoChange => TaskOutcome::Skipped,
+    }
+}
+
+/// Loads the variables from all of a tasks .env files, in the order they should be applied.
+fn load_env_files(task: &TaskInfo) -> Result<Vec<(String, String)>, TaskError> {
+    let mut vars = Vec::new();
+    for path in &task.env_files {
+        let full_path = path.full_path();
+        let iter = dotenvy::from_path_iter(&full_path)
+            .map_err(|e| TaskError::EnvFileError(full_path.clone(), e))?;
+        for var in iter {
+            vars.push(var.map_err(|e| TaskError::EnvFileError(full_path.clone(), e))?);
+        }
+    }
+    Ok(vars)
+}
+
+/// The subset of our environment that a task should see when running hermetically.
+///
+/// Variables with non UTF-8 names can't be matched against the allowlist so are
+/// never passed through, but values are passed through as is.
+fn passthrough_env(task: &TaskInfo, allowlist: &GlobSet) -> Vec<(String, OsString)> {
+    std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value)))
+        .filter(|(name, _)| allowlist.is_match(name) || task.inputs.env_vars.contains(name))
+        .collect()
+}
+
+/// The placeholder in a command that extra arguments from the command line are
+/// substituted into.
+const ARGS_PLACEHOLDER: &str = "{args}";
+
+/// Splits a tasks commands into their individual arguments, inserting any extra
+/// arguments from the command line.
+///
+/// Extra arguments replace any `{args}` placeholders in the commands.  If there are
+/// no placeholders they're appended to the final command instead.  Commands that
+/// consist only of placeholders are dropped when there are no extra arguments.
+fn command_lines(commands: &[String], extra_args: &[String]) -> Vec<Vec<String>> {
+    let has_placeholder = commands.iter().any(|c| c.contains(ARGS_PLACEHOLDER));
+
+    let mut lines = commands
+        .iter()
+        .map(|command| {
+            let mut line = Vec::new();
+            for arg in command.split(' ') {
+                if arg == ARGS_PLACEHOLDER {
+                    line.extend(extra_args.iter().cloned());
+                } else if arg.contains(ARGS_PLACEHOLDER) {
+                    line.push(arg.replace(ARGS_PLACEHOLDER, &extra_args.join(" ")));
+                } else {
+                    line.push(arg.to_owned());
+                }
+            }
+            line
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    if !has_placeholder {
+        if let Some(last) = lines.last_mut() {
+            last.extend(extra_args.iter().cloned());
+        }
+    }
+
+    lines
+}
+
 #[tracing::instrument(
     fields(task = %task.task_ref())
     skip(task, workspace, hash_registry))
@@ -188,20 +311,20 @@ async fn run_task(
 fn should_task_run(
     task: &TaskInfo,
     workspace: &Workspace,
+    extra_args: &[String],
     since: Option<String>,
     hash_registry: &HashRegistry,
 ) -> Result<(bool, Option<Hash>), TaskError> {
-    let project = task.project.lookup(workspace);
+    let root = task.owner.root(workspace);
 
     match since {
         Some(since) => {
-            let project_root = project.root.clone();
-            let should_run = git::have_files_changed(since, project_root.full_path())?;
+            let should_run = git::have_files_changed(since, root.full_path())?;
 
             Ok((should_run, None))
         }
         None => {
-            let new_hash = hash_task_inputs(project, task)?;
+            let new_hash = hash_task_inputs(&root, task, extra_args)?;
             let last_hash = hash_registry
                 .lookup(&task.task_ref())
                 .and_then(|h| h.inputs);
@@ -226,3 +349,108 @@ impl SimplifiedOutcome {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+    use crate::{
+        cli::run_command::{output::build_command_outputs, tests::strings},
+        workspace::tests::a_workspace,
+    };
+
+    #[test]
+    fn test_command_lines_without_extra_args() {
+        assert_eq!(
+            command_lines(&strings(&["cargo build", "cargo test {args}"]), &[]),
+            vec![strings(&["cargo", "build"]), strings(&["cargo", "test"])]
+        );
+    }
+
+    #[test]
+    fn test_command_lines_drops_commands_that_are_only_placeholders() {
+        assert_eq!(
+            command_lines(&strings(&["cargo build", "{args}"]), &[]),
+            vec![strings(&["cargo", "build"])]
+        );
+    }
+
+    #[test]
+    fn test_command_lines_appends_to_final_command() {
+        assert_eq!(
+            command_lines(
+                &strings(&["cargo build", "cargo test"]),
+                &strings(&["--nocapture", "some_filter"])
+            ),
+            vec![
+                strings(&["cargo", "build"]),
+                strings(&["cargo", "test", "--nocapture", "some_filter"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_lines_substitutes_placeholders() {
+        assert_eq!(
+            command_lines(
+                &strings(&["cargo test {args} -- --nocapture", "echo --filter={args}"]),
+                &strings(&["a", "b"])
+            ),
+            vec![
+                strings(&["cargo", "test", "a", "b", "--", "--nocapture"]),
+                strings(&["echo", "--filter=a b"])
+            ]
+        );
+    }
+
+    /// Runs the task `name` from the test-fixtures project, which has no commands,
+    /// after its requirements finished with `requirement_outcome`.
+    async fn run_alias(name: &str, requirement_outcome: SimplifiedOutcome) -> TaskOutcome {
+        let workspace = Arc::new(a_workspace());
+        let task = workspace
+            .project_by_name("test-fixtures")
+            .unwrap()
+            .lookup_task(name, &workspace)
+            .unwrap();
+        assert!(task.commands.is_empty());
+
+        let task_ref = task.task_ref();
+        let hash_registry = Arc::new(HashRegistry::for_workspace(&workspace).unwrap());
+        let settings = RunSettings {
+            since: None,
+            hermetic_env: false,
+            env_passthrough: Arc::new(workspace.env_passthrough_globset()),
+        };
+        let mut runner = TaskRunner::new(
+            &workspace,
+            settings,
+            build_command_outputs(&[task]),
+            HashMap::new(),
+            &hash_registry,
+        );
+        for requirement in task_ref.direct_dependencies(&workspace) {
+            runner.outcomes.insert(requirement, requirement_outcome);
+        }
+
+        runner.start_task(task_ref.clone());
+        let finished = runner.next_finished().await.unwrap();
+        assert_eq!(finished.task_ref, task_ref);
+        finished.outcome
+    }
+
+    #[rstest::rstest]
+    #[case::declared_without_commands("ci")]
+    #[case::commands_filtered_out("deploy")]
+    #[tokio::test]
+    async fn test_alias_outcome_follows_its_requirements(#[case] name: &str) {
+        assert!(matches!(
+            run_alias(name, SimplifiedOutcome::Skipped).await,
+            TaskOutcome::Skipped
+        ));
+        assert!(matches!(
+            run_alias(name, SimplifiedOutcome::Succesful).await,
+            TaskOutcome::Succesful
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 use tabled::{Table, Tabled};
 
-use crate::workspace::Workspace;
+use super::filters::{filter_projects, ProjectFilter};
+use crate::workspace::{DevDependencies, TaskInfo, Workspace};
 
 #[derive(clap::Parser)]
 pub struct TasksOpts {
@@ -11,6 +12,18 @@ pub struct TasksOpts {
     /// Defaults to showing a table if running interactively, plain otherwise.
     #[clap(long, default_value_t = Format::Auto)]
     pub format: Format,
+
+    /// The project or projects to show the tasks of.
+    ///
+    /// Takes the same filters as `nabs run`.  Defaults to every project, along with
+    /// the tasks defined in the workspace.kdl.
+    #[clap(long, visible_alias = "project")]
+    pub filter: Option<ProjectFilter>,
+
+    /// Show the description, commands, inputs and requirements of each task, and
+    /// the file that defined it.
+    #[clap(long)]
+    pub detailed: bool,
 }
 
 #[derive(Clone)]
@@ -23,17 +36,44 @@ pub enum Format {
 }
 
 pub fn run(workspace: Workspace, opts: TasksOpts) -> miette::Result<()> {
-    let outputs = workspace.projects().map(|project| Output {
-        project: project.name.clone(),
-        tasks: project
-            .tasks(&workspace)
-            .into_iter()
-            .map(|t| t.name.as_ref())
-            .collect::<Vec<_>>()
-            .join("\n"),
+    // Tasks from the workspace.kdl don't belong to any project, so only show
+    // them when we're not filtering.
+    let root_tasks = match opts.filter {
+        Some(_) => Vec::new(),
+        None => workspace.root_tasks(),
+    };
+    let mut projects = filter_projects(&workspace, opts.filter, DevDependencies::Include)
+        .into_iter()
+        .collect::<Vec<_>>();
+    projects.sort_by_key(|project| project.project_ref());
+    let owners = (!root_tasks.is_empty())
+        .then(|| ("//".to_owned(), root_tasks))
+        .into_iter()
+        .chain(
+            projects
+                .into_iter()
+                .map(|project| (project.name.clone(), project.tasks(&workspace))),
+        );
+
+    let format = opts.format.actual_format();
+    if opts.detailed {
+        let details = owners.flat_map(|(project, mut tasks)| {
+            tasks.sort_by(|a, b| a.name.cmp(&b.name));
+            tasks
+                .into_iter()
+                .map(|task| TaskDetails::new(&project, task, &workspace))
+                .collect::<Vec<_>>()
+        });
+        print_details(details, format);
+        return Ok(());
+    }
+
+    let outputs = owners.map(|(project, tasks)| Output {
+        project,
+        tasks: task_names(tasks),
     });
 
-    match opts.format.actual_format() {
+    match format {
         ActualFormat::Plain => {
             for project in outputs {
                 print!("{}: {}", project.project, project.tasks.replace('\n', ", "))
@@ -56,12 +96,134 @@ pub fn run(workspace: Workspace, opts: TasksOpts) -> miette::Result<()> {
     Ok(())
 }
 
+fn print_details(details: impl Iterator<Item = TaskDetails>, format: ActualFormat) {
+    match format {
+        ActualFormat::Plain => {
+            for task in details {
+                print!("{task}")
+            }
+        }
+        ActualFormat::Table => {
+            println!("{}", Table::new(details));
+        }
+        ActualFormat::Json => {
+            let details = details.collect::<Vec<_>>();
+            print!("{}", serde_json::to_string(&details).unwrap())
+        }
+        ActualFormat::NdJson => {
+            for task in details {
+                println!("{}", serde_json::to_string(&task).unwrap())
+            }
+        }
+    }
+}
+
+fn task_names(tasks: Vec<&TaskInfo>) -> String {
+    tasks
+        .into_iter()
+        .map(task_label)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A tasks name, along with whether it's disabled, an alias, or has
+/// had all of its commands filtered out by `when` conditions
+fn task_label(task: &TaskInfo) -> String {
+    match &task.disabled {
+        Some(reason) => format!("{} (disabled: {reason})", task.name),
+        None if task.alias => format!("{} (alias)", task.name),
+        None if task.commands.is_empty() => format!("{} (no commands on this machine)", task.name),
+        None => task.name.clone(),
+    }
+}
+
 #[derive(serde::Serialize, Tabled)]
 pub struct Output {
     project: String,
     tasks: String,
 }
 
+#[derive(serde::Serialize, Tabled)]
+pub struct TaskDetails {
+    project: String,
+    task: String,
+    #[tabled(display_with = "display_description")]
+    description: Option<String>,
+    #[tabled(display_with = "display_lines")]
+    commands: Vec<String>,
+    #[tabled(display_with = "display_lines")]
+    inputs: Vec<String>,
+    #[tabled(display_with = "display_lines")]
+    requires: Vec<String>,
+    defined_in: String,
+}
+
+impl TaskDetails {
+    fn new(project: &str, task: &TaskInfo, workspace: &Workspace) -> TaskDetails {
+        let inputs = &task.inputs;
+        let mut requires = task
+            .task_ref()
+            .direct_dependencies(workspace)
+            .into_iter()
+            .map(|required| required.to_string())
+            .collect::<Vec<_>>();
+        requires.sort();
+
+        TaskDetails {
+            project: project.to_owned(),
+            task: task_label(task),
+            description: task.description.clone(),
+            commands: task.commands.clone(),
+            inputs: inputs
+                .paths
+                .iter()
+                .map(|path| format!("path {}", path.glob()))
+                .chain(inputs.env_vars.iter().map(|var| format!("env_var {var}")))
+                .chain(
+                    inputs
+                        .commands
+                        .iter()
+                        .map(|command| format!("command {command}")),
+                )
+                .collect(),
+            requires,
+            defined_in: task.defined_in.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for TaskDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}: {}", self.project, self.task)?;
+        if let Some(description) = &self.description {
+            writeln!(f, "  {description}")?;
+        }
+        writeln!(f, "  defined in {}", self.defined_in)?;
+        for (heading, lines) in [
+            ("commands", &self.commands),
+            ("inputs", &self.inputs),
+            ("requires", &self.requires),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            writeln!(f, "  {heading}:")?;
+            for line in lines {
+                writeln!(f, "    {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn display_description(description: &Option<String>) -> String {
+    description.clone().unwrap_or_default()
+}
+
+fn display_lines(lines: &[String]) -> String {
+    lines.join("\n")
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum ActualFormat {
     Plain,
@@ -109,3 +271,47 @@ impl std::str::FromStr for Format {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::tests::a_workspace;
+
+    fn details(project: &str, task: &str) -> TaskDetails {
+        let workspace = a_workspace();
+        let task = workspace
+            .project_by_name(project)
+            .unwrap()
+            .lookup_task(task, &workspace)
+            .unwrap();
+
+        TaskDetails::new(project, task, &workspace)
+    }
+
+    #[test]
+    fn test_plain_task_details() {
+        insta::assert_display_snapshot!(details("a-lib", "build"));
+    }
+
+    #[test]
+    fn test_json_task_details() {
+        insta::assert_snapshot!(
+            serde_json::to_string_pretty(&details("test-fixtures", "ci")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_task_labels() {
+        let workspace = a_workspace();
+        let fixtures = workspace.project_by_name("test-fixtures").unwrap();
+        let label = |name: &str| task_label(fixtures.lookup_task(name, &workspace).unwrap());
+
+        assert_eq!(label("build"), "build");
+        assert_eq!(label("ci"), "ci (alias)");
+        assert_eq!(label("deploy"), "deploy (no commands on this machine)");
+        assert_eq!(
+            label("seed"),
+            "seed (disabled: NABS_TEST_NEVER_SET isn't set)"
+        );
+    }
+}
@@ -0,0 +1,617 @@
+//! Compares the dependencies declared in project files against the
+//! dependencies in each projects language specific manifests.
+
+use std::ops::Range;
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+
+use super::{
+    parsing::{read_dependencies, ManifestResolver},
+    spanned::Spanned,
+    validated::{DependencyKind, Manifest, ProjectDependency},
+    ValidConfig, ValidPath, ValidProjectFile,
+};
+use crate::diagnostics::{ConfigError, DynDiagnostic};
+
+const MANIFESTS: [Manifest; 3] = [Manifest::Cargo, Manifest::PackageJson, Manifest::GoMod];
+
+/// The differences between a projects dependencies and its manifests
+pub struct DependencyDrift<'a> {
+    pub project_file: &'a ValidProjectFile,
+    differences: Vec<Difference>,
+}
+
+enum Difference {
+    Missing {
+        path: ValidPath,
+        kind: DependencyKind,
+        manifest: Manifest,
+    },
+    Extra {
+        path: Spanned<ValidPath>,
+        node: miette::SourceSpan,
+    },
+    WrongKind {
+        path: Spanned<ValidPath>,
+        node: miette::SourceSpan,
+        declared: DependencyKind,
+        expected: DependencyKind,
+        manifest: Manifest,
+    },
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum DifferenceError {
+    #[error("{project} depends on {dependency} in its {manifest}, but not in its project file")]
+    #[diagnostic(help("run `nabs check deps --fix` to add the dependency"))]
+    Missing {
+        project: String,
+        dependency: ValidPath,
+        manifest: &'static str,
+        #[label("{dependency} should be listed here")]
+        span: miette::SourceSpan,
+    },
+    #[error("{project} depends on {dependency}, but none of its manifests do")]
+    #[diagnostic(help("run `nabs check deps --fix` to remove the dependency"))]
+    Extra {
+        project: String,
+        dependency: ValidPath,
+        #[label("this dependency isn't in any manifest")]
+        span: miette::SourceSpan,
+    },
+    #[error("{dependency} is a {expected} dependency in the {manifest} for {project}")]
+    #[diagnostic(help("run `nabs check deps --fix` to update the dependency"))]
+    WrongKind {
+        project: String,
+        dependency: ValidPath,
+        declared: DependencyKind,
+        expected: DependencyKind,
+        manifest: &'static str,
+        #[label("but it's a {declared} dependency here")]
+        span: miette::SourceSpan,
+    },
+}
+
+/// Finds any projects whose dependencies differ from the dependencies in their manifests.
+///
+/// Only dependencies on other projects in the workspace are compared, and projects
+/// without any manifests are skipped entirely.
+pub fn check_dependencies(config: &ValidConfig) -> Result<Vec<DependencyDrift<'_>>, ConfigError> {
+    let mut resolver = ManifestResolver::new(
+        config.workspace_file.workspace_root.clone(),
+        config
+            .project_files
+            .iter()
+            .map(|project_file| project_file.project_root.clone()),
+    );
+
+    let mut errors = Vec::new();
+    let mut drift = Vec::new();
+    for project_file in &config.project_files {
+        let project_root = &project_file.project_root;
+        let mut expected = Vec::<(ValidPath, DependencyKind, Manifest)>::new();
+        let mut has_manifest = false;
+
+        for manifest in MANIFESTS {
+            if !project_root.full_path().join(manifest.file_name()).exists() {
+                continue;
+            }
+            has_manifest = true;
+
+            let dependencies = match read_dependencies(
+                manifest,
+                project_root,
+                &config.workspace_file.workspace_root,
+                project_file.config.project.span,
+            ) {
+                Ok(dependencies) => dependencies,
+                Err(error) => {
+                    errors.push(
+                        DynDiagnostic::new(error).with_source_code(project_file.source.clone()),
+                    );
+                    continue;
+                }
+            };
+            for dependency in dependencies {
+                let Some(path) = resolver.resolve(dependency.target) else {
+                    continue;
+                };
+                if path != *project_root && !expected.iter().any(|(existing, ..)| *existing == path)
+                {
+                    expected.push((path, dependency.kind, manifest));
+                }
+            }
+        }
+
+        if !has_manifest {
+            continue;
+        }
+
+        let dependencies = &project_file.config.dependencies;
+        let mut differences = Vec::new();
+        for (path, kind, manifest) in &expected {
+            match dependencies
+                .iter()
+                .find(|dependency| *dependency.path == *path)
+            {
+                None => differences.push(Difference::Missing {
+                    path: path.clone(),
+                    kind: *kind,
+                    manifest: *manifest,
+                }),
+                // Dependencies read from a manifest aren't in the project file to be fixed
+                Some(dependency) if dependency.inferred_from.is_some() => {}
+                Some(dependency) if dependency.kind != *kind => {
+                    differences.push(Difference::WrongKind {
+                        path: dependency.path.clone(),
+                        node: dependency.span,
+                        declared: dependency.kind,
+                        expected: *kind,
+                        manifest: *manifest,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for dependency in declared_dependencies(&project_file.config) {
+            if !expected.iter().any(|(path, ..)| *path == *dependency.path) {
+                differences.push(Difference::Extra {
+                    path: dependency.path.clone(),
+                    node: dependency.span,
+                });
+            }
+        }
+
+        if !differences.is_empty() {
+            drift.push(DependencyDrift {
+                project_file,
+                differences,
+            });
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ConfigError { errors });
+    }
+
+    Ok(drift)
+}
+
+impl DependencyDrift<'_> {
+    pub fn diagnostics(&self) -> Vec<DynDiagnostic> {
+        let config = &self.project_file.config;
+        let project = config.project.to_string();
+
+        self.differences
+            .iter()
+            .map(|difference| {
+                let error = match difference {
+                    Difference::Missing { path, manifest, .. } => DifferenceError::Missing {
+                        project: project.clone(),
+                        dependency: path.clone(),
+                        manifest: manifest.file_name(),
+                        span: config.dependencies_span.unwrap_or(config.project.span),
+                    },
+                    Difference::Extra { path, .. } => DifferenceError::Extra {
+                        project: project.clone(),
+                        dependency: (**path).clone(),
+                        span: path.span,
+                    },
+                    Difference::WrongKind {
+                        path,
+                        declared,
+                        expected,
+                        manifest,
+                        ..
+                    } => DifferenceError::WrongKind {
+                        project: project.clone(),
+                        dependency: (**path).clone(),
+                        declared: *declared,
+                        expected: *expected,
+                        manifest: manifest.file_name(),
+                        span: path.span,
+                    },
+                };
+                DynDiagnostic::new(error).with_source_code(self.project_file.source.clone())
+            })
+            .collect()
+    }
+
+    /// The contents of the project file, updated so its dependencies match its manifests.
+    ///
+    /// This edits the file line by line, so expects each dependency to be on a line of its own.
+    pub fn fixed_source(&self) -> String {
+        let source = self.project_file.source.contents();
+        let config = &self.project_file.config;
+
+        let mut edits = Vec::<(Range<usize>, String)>::new();
+        let mut missing = Vec::new();
+        for difference in &self.differences {
+            match difference {
+                Difference::Missing { path, kind, .. } => missing.push((path, *kind)),
+                Difference::Extra { node, .. } => {
+                    edits.push((removal_range(source, *node), String::new()));
+                }
+                Difference::WrongKind {
+                    path,
+                    node,
+                    expected,
+                    ..
+                } => {
+                    let declared_path =
+                        &source[path.span.offset()..path.span.offset() + path.span.len()];
+                    edits.push((
+                        node_range(source, *node),
+                        dependency_node(declared_path, *expected),
+                    ));
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            let project_root = self.project_file.project_root.as_subpath();
+            let nodes = missing
+                .iter()
+                .map(|(path, kind)| {
+                    let path = format!("\"{}\"", relative_path(project_root, path.as_subpath()));
+                    dependency_node(&path, *kind)
+                })
+                .collect::<Vec<_>>();
+            edits.push(add_dependencies(source, config, &nodes));
+        }
+
+        // Applying the edits from the end of the file backwards keeps the earlier offsets valid
+        edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+        let mut fixed = source.to_owned();
+        for (range, replacement) in edits {
+            fixed.replace_range(range, &replacement);
+        }
+        fixed
+    }
+}
+
+/// An edit that adds some dependency nodes to the end of the projects dependencies
+/// block, or creates a block if there isn't one.
+fn add_dependencies(
+    source: &str,
+    config: &super::ProjectDefinition,
+    nodes: &[String],
+) -> (Range<usize>, String) {
+    let Some(block) = config.dependencies_span else {
+        let after_project = line_range(source, config.project.span.offset()).end;
+        let mut addition = String::from("\ndependencies {\n");
+        for node in nodes {
+            addition.push_str(&format!("    {node}\n"));
+        }
+        addition.push_str("}\n");
+        return (after_project..after_project, addition);
+    };
+
+    let block_indent = indentation(&source[line_range(source, block.offset())]);
+    let indent = match declared_dependencies(config).last() {
+        Some(last) if starts_line(source, last.span.offset()) => {
+            indentation(&source[line_range(source, last.span.offset())]).to_owned()
+        }
+        _ => format!("{block_indent}    "),
+    };
+    let lines = nodes
+        .iter()
+        .map(|node| format!("{indent}{node}\n"))
+        .collect::<String>();
+
+    let block_text = &source[block.offset()..block.offset() + block.len()];
+    let close = block.offset() + block_text.rfind('}').expect("a block to end with }");
+    let close_line = line_range(source, close);
+    if source[close_line.start..close].trim().is_empty() {
+        // The closing brace is on a line of its own, so we can add lines above it
+        return (close_line.start..close_line.start, lines);
+    }
+
+    let content_end = source[..close].trim_end().len();
+    (content_end..close, format!("\n{lines}{block_indent}"))
+}
+
+/// The dependencies listed explicitly in a project file, rather than read from a manifest
+fn declared_dependencies(config: &super::ProjectDefinition) -> Vec<&ProjectDependency> {
+    config
+        .dependencies
+        .iter()
+        .filter(|dependency| dependency.inferred_from.is_none())
+        .collect()
+}
+
+fn dependency_node(path: &str, kind: DependencyKind) -> String {
+    match kind {
+        DependencyKind::Runtime => format!("project {path}"),
+        kind => format!("project {path} kind=\"{kind}\""),
+    }
+}
+
+/// The range of a node, without any `;` terminating it
+fn node_range(source: &str, node: miette::SourceSpan) -> Range<usize> {
+    let text = &source[node.offset()..node.offset() + node.len()];
+    let text = text.trim_end().trim_end_matches(';').trim_end();
+    node.offset()..node.offset() + text.len()
+}
+
+/// The range to remove to delete a node.
+///
+/// This is the nodes whole line if it's on a line of its own, otherwise it's the
+/// node, its terminator, and the whitespace separating it from its neighbours.
+fn removal_range(source: &str, node: miette::SourceSpan) -> Range<usize> {
+    let line = line_range(source, node.offset());
+    let mut end = node_range(source, node).end;
+    if source[end..].starts_with(';') {
+        end += 1;
+    }
+    if !starts_line(source, node.offset()) {
+        let start = source[..node.offset()].trim_end_matches([' ', '\t']).len();
+        return start..end;
+    }
+    if source[end..line.end].trim().is_empty() {
+        return line;
+    }
+    let next = source[end..].trim_start_matches([' ', '\t']);
+    node.offset()..source.len() - next.len()
+}
+
+/// Whether offset is the first non-whitespace character on its line
+fn starts_line(source: &str, offset: usize) -> bool {
+    source[line_range(source, offset).start..offset]
+        .trim()
+        .is_empty()
+}
+
+/// The range of the line containing offset, including its newline
+fn line_range(source: &str, offset: usize) -> Range<usize> {
+    let start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let end = source[offset..]
+        .find('\n')
+        .map(|i| offset + i + 1)
+        .unwrap_or(source.len());
+    start..end
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// The path to `to` from the directory `from`, where both are relative to the workspace root
+fn relative_path(from: &Utf8Path, to: &Utf8Path) -> Utf8PathBuf {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = Utf8PathBuf::new();
+    for _ in common..from.len() {
+        path.push(Utf8Component::ParentDir);
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::{GraphicalReportHandler, GraphicalTheme};
+    use similar_asserts::assert_eq;
+
+    use super::*;
+    use crate::{config::load_config_from_path, test_files::TestFiles};
+
+    fn workspace_files() -> TestFiles {
+        TestFiles::new()
+            .with_file(
+                "workspace.kdl",
+                r#"
+                    name "my-workspace"
+                    project_path "**"
+                "#,
+            )
+            .with_file("lib/project.kdl", r#"project "lib""#)
+            .with_file("fixtures/project.kdl", r#"project "fixtures""#)
+            .with_file("docs/project.kdl", r#"project "docs""#)
+            .with_file(
+                "service/Cargo.toml",
+                r#"
+                    [package]
+                    name = "service"
+
+                    [dependencies]
+                    lib = { path = "../lib" }
+
+                    [dev-dependencies]
+                    fixtures = { path = "../fixtures" }
+                "#,
+            )
+    }
+
+    fn fixed_project_file(test_files: &TestFiles) -> String {
+        let config = load_config_from_path(test_files.root().into()).unwrap();
+        let drift = check_dependencies(&config).unwrap();
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].project_file.config.project.as_str(), "service");
+
+        drift[0].fixed_source()
+    }
+
+    fn drifting_service_files() -> TestFiles {
+        workspace_files().with_file(
+            "service/project.kdl",
+            r#"
+                project "service"
+
+                dependencies {
+                    project "../fixtures"
+                    project "../docs"
+                }
+            "#,
+        )
+    }
+
+    #[test]
+    fn test_drift_diagnostics() {
+        let test_files = drifting_service_files();
+        let config = load_config_from_path(test_files.root().into()).unwrap();
+        let drift = check_dependencies(&config).unwrap();
+        assert_eq!(drift.len(), 1);
+
+        let diagnostics = drift[0]
+            .diagnostics()
+            .into_iter()
+            .map(|diagnostic| {
+                let mut report = String::new();
+                GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+                    .render_report(&mut report, &diagnostic)
+                    .unwrap();
+                report
+            })
+            .collect::<Vec<_>>();
+
+        insta::assert_display_snapshot!(diagnostics.join("\n"));
+    }
+
+    #[test]
+    fn test_fixing_dependencies() {
+        let test_files = drifting_service_files();
+
+        assert_eq!(
+            fixed_project_file(&test_files),
+            unindent::unindent(
+                r#"
+                project "service"
+
+                dependencies {
+                    project "../fixtures" kind="dev"
+                    project "../lib"
+                }
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_fixing_dependencies_without_a_block() {
+        let test_files = workspace_files().with_file(
+            "service/project.kdl",
+            r#"
+                project "service"
+
+                tags "rust"
+            "#,
+        );
+
+        assert_eq!(
+            fixed_project_file(&test_files),
+            unindent::unindent(
+                r#"
+                project "service"
+
+                dependencies {
+                    project "../lib"
+                    project "../fixtures" kind="dev"
+                }
+
+                tags "rust"
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_fixing_dependencies_on_one_line() {
+        let test_files = workspace_files().with_file(
+            "service/project.kdl",
+            r#"
+                project "service"
+                dependencies { project "../docs"; }
+            "#,
+        );
+
+        assert_eq!(
+            fixed_project_file(&test_files),
+            unindent::unindent(
+                r#"
+                project "service"
+                dependencies {
+                    project "../lib"
+                    project "../fixtures" kind="dev"
+                }
+                "#
+            )
+        );
+
+        let test_files = workspace_files().with_file(
+            "service/project.kdl",
+            r#"
+                project "service"
+                dependencies { project "../docs"; project "../fixtures"; }
+            "#,
+        );
+
+        assert_eq!(
+            fixed_project_file(&test_files),
+            unindent::unindent(
+                r#"
+                project "service"
+                dependencies { project "../fixtures" kind="dev";
+                    project "../lib"
+                }
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_fixing_leaves_manifest_directives_alone() {
+        let test_files = workspace_files()
+            .with_file("lib/package.json", r#"{ "name": "@workspace/lib" }"#)
+            .with_file(
+                "service/package.json",
+                r#"
+                    {
+                        "name": "service",
+                        "devDependencies": { "@workspace/lib": "workspace:*" }
+                    }
+                "#,
+            )
+            .with_file(
+                "service/project.kdl",
+                r#"
+                    project "service"
+
+                    dependencies {
+                        from_package_json
+                        project "../docs"
+                    }
+                "#,
+            );
+
+        assert_eq!(
+            fixed_project_file(&test_files),
+            unindent::unindent(
+                r#"
+                project "service"
+
+                dependencies {
+                    from_package_json
+                    project "../fixtures" kind="dev"
+                }
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path("projects/a-service".into(), "projects/a-lib".into()),
+            Utf8PathBuf::from("../a-lib")
+        );
+        assert_eq!(
+            relative_path("service".into(), "service/nested".into()),
+            Utf8PathBuf::from("nested")
+        );
+    }
+}
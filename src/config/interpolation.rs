@@ -0,0 +1,351 @@
+use std::{collections::BTreeMap, fmt};
+
+use camino::Utf8Path;
+
+use super::spanned::Spanned;
+
+/// A string from a config file that may contain `{variable}` placeholders.
+///
+/// The placeholders are checked when config is validated, but can only be
+/// rendered once we know which project & task they're being used in.
+#[derive(Clone)]
+pub struct InterpolatedString {
+    raw: String,
+    segments: Vec<Segment>,
+    pub span: miette::SourceSpan,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Variable(Variable),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Variable {
+    ProjectName,
+    ProjectRoot,
+    WorkspaceRoot,
+    TaskName,
+    Env(String),
+    /// Extra arguments from the command line.  These aren't known until a task
+    /// is run, so we leave this placeholder in place for `nabs run` to handle.
+    Args,
+    /// A variable declared by a template.  These are substituted when the
+    /// template is applied to a project, so should never be rendered.
+    Template(String),
+    /// One of the values a matrix task is being run with.
+    Matrix(String),
+}
+
+/// The values that variables can be rendered with.
+pub struct InterpolationContext<'a> {
+    pub project_name: &'a str,
+    pub project_root: &'a Utf8Path,
+    pub workspace_root: &'a Utf8Path,
+    pub task_name: &'a str,
+    /// The matrix values of the task instance being rendered
+    pub matrix: &'a BTreeMap<String, String>,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum InterpolationError {
+    #[error("Unknown variable {{{name}}}")]
+    #[diagnostic(help(
+        "the available variables are project.name, project.root, workspace.root, task.name, env.<NAME>, var.<NAME>, matrix.<NAME> and args"
+    ))]
+    UnknownVariable {
+        name: String,
+        #[label("this string references {{{name}}}")]
+        span: miette::SourceSpan,
+    },
+}
+
+impl InterpolatedString {
+    pub fn parse(value: Spanned<String>) -> Result<Self, Vec<InterpolationError>> {
+        use chumsky::Parser;
+
+        let span = value.span;
+        let raw = value.into_inner();
+
+        let parsed = parser::parser()
+            .parse(raw.as_str())
+            .expect("the interpolation parser to accept any string");
+
+        let mut segments = Vec::<Segment>::new();
+        let mut errors = Vec::new();
+        for segment in parsed {
+            let segment = match segment {
+                parser::ParsedSegment::Literal(c) => {
+                    if let Some(Segment::Literal(literal)) = segments.last_mut() {
+                        literal.push(c);
+                        continue;
+                    }
+                    Segment::Literal(c.to_string())
+                }
+                parser::ParsedSegment::Placeholder(name) => match Variable::from_name(&name) {
+                    Some(variable) => Segment::Variable(variable),
+                    None => {
+                        errors.push(InterpolationError::UnknownVariable { name, span });
+                        continue;
+                    }
+                },
+            };
+            segments.push(segment);
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(InterpolatedString {
+            raw,
+            segments,
+            span,
+        })
+    }
+
+    /// The names of any template variables this string references
+    pub fn template_variables(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Variable(Variable::Template(name)) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The names of any matrix variables this string references
+    pub fn matrix_variables(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Variable(Variable::Matrix(name)) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Replaces any template variables in this string with the provided values.
+    pub fn substitute(&self, values: &BTreeMap<String, InterpolatedString>) -> InterpolatedString {
+        let mut raw = self.raw.clone();
+        let mut segments = Vec::with_capacity(self.segments.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Variable(Variable::Template(name)) if values.contains_key(name) => {
+                    let value = &values[name];
+                    raw = raw.replace(&format!("{{var.{name}}}"), &value.raw);
+                    segments.extend(value.segments.iter().cloned());
+                }
+                other => segments.push(other.clone()),
+            }
+        }
+
+        InterpolatedString {
+            raw,
+            segments,
+            span: self.span,
+        }
+    }
+
+    pub fn render(&self, context: &InterpolationContext<'_>) -> String {
+        let mut output = String::with_capacity(self.raw.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => output.push_str(literal),
+                Segment::Variable(variable) => output.push_str(&variable.render(context)),
+            }
+        }
+        output
+    }
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Variable> {
+        Some(match name {
+            "project.name" => Variable::ProjectName,
+            "project.root" => Variable::ProjectRoot,
+            "workspace.root" => Variable::WorkspaceRoot,
+            "task.name" => Variable::TaskName,
+            "args" => Variable::Args,
+            _ => {
+                if let Some(name) = name.strip_prefix("var.") {
+                    Variable::Template(name.to_owned())
+                } else if let Some(name) = name.strip_prefix("matrix.") {
+                    Variable::Matrix(name.to_owned())
+                } else {
+                    Variable::Env(name.strip_prefix("env.")?.to_owned())
+                }
+            }
+        })
+    }
+
+    fn render(&self, context: &InterpolationContext<'_>) -> String {
+        match self {
+            Variable::ProjectName => context.project_name.to_owned(),
+            Variable::ProjectRoot => context.project_root.to_string(),
+            Variable::WorkspaceRoot => context
+                .workspace_root
+                .as_str()
+                .trim_end_matches('/')
+                .to_owned(),
+            Variable::TaskName => context.task_name.to_owned(),
+            Variable::Env(name) => std::env::var(name).unwrap_or_default(),
+            Variable::Args => "{args}".to_owned(),
+            Variable::Template(name) => {
+                unreachable!(
+                    "template variable {name} should have been substituted before rendering"
+                )
+            }
+            Variable::Matrix(name) => context
+                .matrix
+                .get(name)
+                .cloned()
+                .expect("matrix variables to be checked against the tasks matrix"),
+        }
+    }
+}
+
+impl fmt::Debug for InterpolatedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.raw.fmt(f)
+    }
+}
+
+mod parser {
+    use chumsky::prelude::*;
+
+    #[derive(Clone, Debug)]
+    pub enum ParsedSegment {
+        Literal(char),
+        Placeholder(String),
+    }
+
+    pub fn parser() -> impl chumsky::Parser<char, Vec<ParsedSegment>, Error = Simple<char>> {
+        let is_variable_char =
+            |c: &char| c.is_alphanumeric() || *c == '_' || *c == '-' || *c == '.';
+
+        // Anything that doesn't look like a variable (e.g. a glob alternation
+        // like `{a,b}`) is left alone as a literal.
+        let placeholder = filter(is_variable_char)
+            .repeated()
+            .at_least(1)
+            .collect::<String>()
+            .delimited_by(just('{'), just('}'))
+            .map(ParsedSegment::Placeholder);
+
+        let literal = any().map(ParsedSegment::Literal);
+
+        placeholder.or(literal).repeated().then_ignore(end())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use similar_asserts::assert_eq;
+
+    use super::*;
+    use crate::config::spanned::WithSpan;
+
+    fn parse(s: &str) -> Result<InterpolatedString, Vec<InterpolationError>> {
+        InterpolatedString::parse(s.to_string().with_span((0, s.len()).into()))
+    }
+
+    fn context() -> InterpolationContext<'static> {
+        static NO_MATRIX: BTreeMap<String, String> = BTreeMap::new();
+        InterpolationContext {
+            project_name: "a-service",
+            project_root: Utf8Path::new("/workspace/projects/a-service"),
+            workspace_root: Utf8Path::new("/workspace/"),
+            task_name: "build",
+            matrix: &NO_MATRIX,
+        }
+    }
+
+    #[test]
+    fn test_rendering_variables() {
+        let string =
+            parse("echo {task.name} {project.name} {project.root} {workspace.root}").unwrap();
+
+        assert_eq!(
+            string.render(&context()),
+            "echo build a-service /workspace/projects/a-service /workspace"
+        );
+    }
+
+    #[test]
+    fn test_rendering_env_vars() {
+        std::env::set_var("NABS_INTERPOLATION_TEST", "hello");
+        let string = parse("{env.NABS_INTERPOLATION_TEST}/{env.NABS_DEFINITELY_UNSET}").unwrap();
+
+        assert_eq!(string.render(&context()), "hello/");
+    }
+
+    #[test]
+    fn test_args_are_left_in_place() {
+        let string = parse("cargo test {args}").unwrap();
+
+        assert_eq!(string.render(&context()), "cargo test {args}");
+    }
+
+    #[test]
+    fn test_non_variable_braces_are_literals() {
+        let string = parse("src/{a,b}/*.rs {} {").unwrap();
+
+        assert_eq!(string.render(&context()), "src/{a,b}/*.rs {} {");
+    }
+
+    #[test]
+    fn test_substituting_template_variables() {
+        let string = parse("cargo build -p {var.crate} --target-dir {var.dir}").unwrap();
+        assert_eq!(
+            string.template_variables().collect::<Vec<_>>(),
+            vec!["crate", "dir"]
+        );
+
+        let values = maplit::btreemap! {
+            "crate".to_string() => parse("my-crate").unwrap(),
+            "dir".to_string() => parse("{workspace.root}/target").unwrap(),
+        };
+        let string = string.substitute(&values);
+
+        assert_eq!(string.template_variables().count(), 0);
+        assert_eq!(
+            format!("{string:?}"),
+            r#""cargo build -p my-crate --target-dir {workspace.root}/target""#
+        );
+        assert_eq!(
+            string.render(&context()),
+            "cargo build -p my-crate --target-dir /workspace/target"
+        );
+    }
+
+    #[test]
+    fn test_rendering_matrix_variables() {
+        let string = parse("cargo build --target {matrix.target}").unwrap();
+        assert_eq!(
+            string.matrix_variables().collect::<Vec<_>>(),
+            vec!["target"]
+        );
+
+        let matrix = maplit::btreemap! {
+            "target".to_string() => "wasm32-unknown-unknown".to_string(),
+        };
+        let context = InterpolationContext {
+            matrix: &matrix,
+            ..context()
+        };
+
+        assert_eq!(
+            string.render(&context),
+            "cargo build --target wasm32-unknown-unknown"
+        );
+    }
+
+    #[test]
+    fn test_unknown_variables() {
+        assert_matches!(parse("echo {project.nme} {blah}").unwrap_err().as_slice(), [
+            InterpolationError::UnknownVariable { name: first, .. },
+            InterpolationError::UnknownVariable { name: second, .. },
+        ] => {
+            assert_eq!(first, "project.nme");
+            assert_eq!(second, "blah");
+        });
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use camino::{Utf8Path, Utf8PathBuf};
 use globset::{Glob, GlobSetBuilder};
 use ignore::WalkBuilder;
@@ -5,9 +7,12 @@ use ignore::WalkBuilder;
 use super::{
     parsing::{parse_project_file, parse_task_file, parse_workspace_file, Validator},
     paths::{RelativePath, ValidPath, WorkspaceRoot},
+    spanned::Spanned,
+    validated::{TaskBlock, TaskDefinition, TaskImport, TaskTemplate, TemplateUse},
     ConfigSource, UnvalidatedConfig, UnvalidatedProjectFile, UnvalidatedWorkspaceFile, ValidConfig,
-    ValidProjectFile,
+    ValidProjectFile, WorkspaceFile,
 };
+use crate::diagnostics::DynDiagnostic;
 
 #[cfg(test)]
 mod tests;
@@ -57,12 +62,42 @@ pub fn load_config_from_path(current_path: Utf8PathBuf) -> Result<ValidConfig, m
 
     let mut config = validator.validate_config(unvalidated)?;
 
+    validator.infer_dependencies(&mut config.project_files);
+
+    for project in &mut config.project_files {
+        import_tasks(project, &mut validator)?;
+    }
+
+    let mut invalid_blocks = HashSet::new();
+    let workspace_templates =
+        workspace_templates(&config.workspace_file, &mut invalid_blocks, &mut validator)?;
+
+    let tagged_templates = tagged_templates(&config.project_files, workspace_templates);
+    let mut invalid_templates = HashSet::new();
     for project in &mut config.project_files {
-        import_tasks(project, &mut validator)?
+        add_tagged_templates(
+            project,
+            &tagged_templates,
+            &mut invalid_templates,
+            &mut validator,
+        );
+        validator.apply_templates(&mut project.config.tasks);
+    }
+
+    for project in &mut config.project_files {
+        add_workspace_tasks(
+            project,
+            &config.workspace_file,
+            &mut invalid_blocks,
+            &mut validator,
+        )?;
+        validator.resolve_task_overrides(&mut project.config.tasks);
     }
 
     validator.ok()?;
 
+    config.warnings = validator.take_warnings();
+
     Ok(config)
 }
 
@@ -171,13 +206,214 @@ pub enum TaskImportError {
     ParsingError(super::ParsingError),
 }
 
+#[derive(Debug, miette::Diagnostic, thiserror::Error)]
+#[error("Task files import each other in a cycle: {}", chain.join(" → "))]
+#[diagnostic(help("remove one of these imports to break the cycle"))]
+pub struct ImportCycleError {
+    chain: Vec<String>,
+    #[related]
+    imports: Vec<DynDiagnostic>,
+}
+
+#[derive(Debug, miette::Diagnostic, thiserror::Error)]
+#[error("{path} is imported here")]
+struct CyclicImport {
+    path: String,
+    #[label("this import is part of the cycle")]
+    span: miette::SourceSpan,
+}
+
+/// A task file that's in the middle of being imported, and the import that led to it.
+struct ImportStep {
+    path: Spanned<ValidPath>,
+    imported_from: ConfigSource,
+}
+
 pub(super) fn import_tasks(
     project_file: &mut ValidProjectFile,
     validator: &mut Validator,
 ) -> Result<(), miette::Report> {
-    let mut imports = std::mem::take(&mut project_file.config.tasks.imports);
+    let imports = std::mem::take(&mut project_file.config.tasks.imports);
+
+    let imported = resolve_imports(
+        imports,
+        &project_file.source,
+        &project_file.project_root,
+        validator,
+    )?;
+
+    let tasks_block = &mut project_file.config.tasks;
+    tasks_block.templates.extend(imported.templates);
+    tasks_block.uses.extend(imported.uses);
+    tasks_block.tasks.extend(imported.tasks);
+
+    Ok(())
+}
+
+/// Loads a set of imports, returning everything they contain in a single `TaskBlock`
+fn resolve_imports(
+    imports: Vec<TaskImport>,
+    imported_from: &ConfigSource,
+    project_root: &ValidPath,
+    validator: &mut Validator,
+) -> Result<TaskBlock, miette::Report> {
+    let mut importer = TaskImporter {
+        project_root: project_root.clone(),
+        validator,
+        chain: Vec::new(),
+        imported: HashSet::new(),
+        templates: Vec::new(),
+        uses: Vec::new(),
+    };
+
+    let mut tasks = Vec::new();
+    for import in imports {
+        tasks.extend(importer.import(import, imported_from, "")?);
+    }
+
+    Ok(TaskBlock {
+        templates: importer.templates,
+        uses: importer.uses,
+        tasks,
+        ..TaskBlock::default()
+    })
+}
+
+/// Adds any tasks from the workspace file that apply to this project.
+///
+/// Tasks that the project has excluded are skipped, and any excludes that don't match
+/// a workspace task are warned about.  Tasks that clash with one the project already
+/// has are left for `Validator::resolve_task_overrides` to deal with.
+/// Any blocks of tasks that fail validation are added to `invalid_blocks`
+/// so we only report their errors once, rather than once per project.
+fn add_workspace_tasks(
+    project_file: &mut ValidProjectFile,
+    workspace_file: &WorkspaceFile,
+    invalid_blocks: &mut HashSet<usize>,
+    validator: &mut Validator,
+) -> Result<(), miette::Report> {
+    let workspace_root = ValidPath::from(workspace_file.workspace_root.clone());
+    let project_subpath = project_file.project_root.as_subpath();
+    let mut workspace_task_names = HashSet::new();
+
+    for (index, workspace_tasks) in workspace_file.config.tasks.iter().enumerate() {
+        if invalid_blocks.contains(&index) {
+            continue;
+        }
+        if let Some(glob) = &workspace_tasks.projects {
+            if !glob
+                .clone()
+                .into_inner()
+                .compile_matcher()
+                .is_match(project_subpath)
+            {
+                continue;
+            }
+        }
+        if let Some(tag) = &workspace_tasks.tag {
+            if !project_file.config.tags.contains(tag) {
+                continue;
+            }
+        }
+
+        let Some(block) = validator.validate_tasks(
+            workspace_tasks.task_block(),
+            &workspace_root,
+            &project_file.project_root,
+            &workspace_file.source,
+        ) else {
+            invalid_blocks.insert(index);
+            continue;
+        };
+
+        let mut tasks = resolve_imports(
+            block.imports,
+            &workspace_file.source,
+            &project_file.project_root,
+            validator,
+        )?;
+        tasks.templates.extend(block.templates);
+        tasks.tasks.extend(block.tasks);
+        validator.apply_templates(&mut tasks);
+        workspace_task_names.extend(tasks.tasks.iter().map(|task| task.name.to_string()));
+
+        let project_tasks = &mut project_file.config.tasks;
+        project_tasks
+            .tasks
+            .extend(tasks.tasks.into_iter().filter(|task| {
+                !project_tasks
+                    .excludes
+                    .iter()
+                    .any(|exclude| exclude.as_str() == task.name.as_str())
+            }));
+    }
+
+    validator.check_excludes(project_file, &workspace_task_names);
+
+    Ok(())
+}
+
+struct TaskImporter<'a> {
+    project_root: ValidPath,
+    validator: &'a mut Validator,
+    /// The files we're currently importing, used to detect cycles.
+    chain: Vec<ImportStep>,
+    /// The files we've imported so far, along with the namespace they were imported into
+    imported: HashSet<(ValidPath, String)>,
+    templates: Vec<TaskTemplate>,
+    uses: Vec<TemplateUse>,
+}
+
+impl TaskImporter<'_> {
+    /// Imports a task file along with anything it imports, returning the tasks found.
+    ///
+    /// The returned tasks have any namespace from `import` applied, but `namespace`
+    /// (the namespace of the file doing the importing) is only used to de-duplicate imports.
+    fn import(
+        &mut self,
+        import: TaskImport,
+        imported_from: &ConfigSource,
+        namespace: &str,
+    ) -> Result<Vec<TaskDefinition>, miette::Report> {
+        let task_path = import.path;
+
+        if let Some(cycle_start) = self.chain.iter().position(|step| *step.path == *task_path) {
+            let cycle = &self.chain[cycle_start..];
+            let chain = cycle
+                .iter()
+                .map(|step| &step.path)
+                .chain([&task_path])
+                .map(|path| path.to_string())
+                .collect();
+            let imports = cycle[1..]
+                .iter()
+                .map(|step| (&step.path, &step.imported_from))
+                .chain([(&task_path, imported_from)])
+                .map(|(path, source)| {
+                    DynDiagnostic::new(CyclicImport {
+                        path: path.to_string(),
+                        span: path.span,
+                    })
+                    .with_source_code(source.clone())
+                })
+                .collect();
+
+            return Err(ImportCycleError { chain, imports }.into());
+        }
+
+        let namespace = match &import.namespace {
+            Some(import_namespace) => format!("{namespace}{import_namespace}."),
+            None => namespace.to_owned(),
+        };
+
+        // Diamond imports are fine, but we only want to pull in each file once.
+        if !self
+            .imported
+            .insert(((*task_path).clone(), namespace.clone()))
+        {
+            return Ok(vec![]);
+        }
 
-    while let Some(task_path) = imports.pop() {
         let task_file_contents = std::fs::read_to_string(task_path.full_path())
             .map_err(|e| TaskImportError::IoError(task_path.as_subpath().to_owned(), e))?;
 
@@ -185,15 +421,168 @@ pub(super) fn import_tasks(
             parse_task_file(task_path.as_subpath(), &task_file_contents)
                 .map_err(TaskImportError::ParsingError)?;
 
-        let config_source = super::ConfigSource::new(task_path.as_subpath(), task_file_contents);
+        let config_source = ConfigSource::new(task_path.as_subpath(), task_file_contents);
+
+        let config = self.validator.validate_tasks(
+            config,
+            &task_path.parent().unwrap(),
+            &self.project_root,
+            &config_source,
+        );
+
+        let Some(config) = config else {
+            return Ok(vec![]);
+        };
+
+        self.chain.push(ImportStep {
+            path: task_path,
+            imported_from: imported_from.clone(),
+        });
+        let mut tasks = Vec::new();
+        for nested_import in config.imports {
+            tasks.extend(self.import(nested_import, &config_source, &namespace)?);
+        }
+        self.chain.pop();
+
+        self.templates.extend(config.templates);
+        self.uses.extend(config.uses);
+        tasks.extend(config.tasks);
 
-        let config = validator.validate_tasks(config, &task_path.parent().unwrap(), &config_source);
+        if let Some(import_namespace) = import.namespace {
+            let names = tasks
+                .iter()
+                .map(|task| task.name.to_string())
+                .collect::<HashSet<_>>();
 
-        if let Some(config) = config {
-            imports.extend(config.imports.into_iter());
-            project_file.config.tasks.tasks.extend(config.tasks);
+            tasks = tasks
+                .into_iter()
+                .map(|task| task.with_namespace(&import_namespace, &names))
+                .collect();
         }
+
+        Ok(tasks)
     }
+}
 
-    Ok(())
+/// Finds the templates declared in the workspace files task blocks, or in files they import.
+///
+/// This is how templates targeting tags are usually shared, as a shared task file
+/// doesn't need to be imported by any project for its targeted templates to apply.
+/// Blocks that fail validation are added to `invalid_blocks` so their errors aren't
+/// reported again when the blocks tasks are added to projects.
+fn workspace_templates(
+    workspace_file: &WorkspaceFile,
+    invalid_blocks: &mut HashSet<usize>,
+    validator: &mut Validator,
+) -> Result<Vec<TaskTemplate>, miette::Report> {
+    let workspace_root = ValidPath::from(workspace_file.workspace_root.clone());
+
+    let mut templates = Vec::new();
+    for (index, workspace_tasks) in workspace_file.config.tasks.iter().enumerate() {
+        let Some(block) = validator.validate_tasks(
+            workspace_tasks.task_block(),
+            &workspace_root,
+            &workspace_root,
+            &workspace_file.source,
+        ) else {
+            invalid_blocks.insert(index);
+            continue;
+        };
+
+        let imported = resolve_imports(
+            block.imports,
+            &workspace_file.source,
+            &workspace_root,
+            validator,
+        )?;
+        templates.extend(block.templates);
+        templates.extend(imported.templates);
+    }
+
+    Ok(templates)
+}
+
+/// Finds all the templates that target tags, from any of the projects task files
+/// or from the workspace file.
+fn tagged_templates(
+    project_files: &[ValidProjectFile],
+    workspace_templates: Vec<TaskTemplate>,
+) -> Vec<TaskTemplate> {
+    let mut templates = Vec::<TaskTemplate>::new();
+    for template in workspace_templates.into_iter().chain(
+        project_files
+            .iter()
+            .flat_map(|project| project.config.tasks.templates.iter().cloned()),
+    ) {
+        if template.targets_tags.is_empty() {
+            continue;
+        }
+        if !templates
+            .iter()
+            .any(|existing| existing.is_same_definition(&template))
+        {
+            templates.push(template);
+        }
+    }
+    templates
+}
+
+/// Applies any templates targeting one of this projects tags, as if the project
+/// had imported the template and used it.
+///
+/// The tasks of any templates that target tags are validated relative to this project.
+/// Templates that fail validation are added to `invalid_templates` so we only report
+/// their errors once, rather than once per project.
+fn add_tagged_templates(
+    project_file: &mut ValidProjectFile,
+    templates: &[TaskTemplate],
+    invalid_templates: &mut HashSet<(String, usize)>,
+    validator: &mut Validator,
+) {
+    let tags = &project_file.config.tags;
+    let tasks = &mut project_file.config.tasks;
+
+    for template in templates {
+        if !template.targets_tags.iter().any(|tag| tags.contains(tag)) {
+            continue;
+        }
+        if tasks
+            .uses
+            .iter()
+            .any(|template_use| template_use.template.as_str() == template.name.as_str())
+        {
+            continue;
+        }
+        if !tasks
+            .templates
+            .iter()
+            .any(|existing| existing.is_same_definition(template))
+        {
+            tasks.templates.push(template.clone());
+        }
+        tasks.uses.push(TemplateUse {
+            template: template.name.clone(),
+            variables: Default::default(),
+            source: template.source.clone(),
+        });
+    }
+
+    for template in &mut tasks.templates {
+        if template.targets_tags.is_empty() {
+            continue;
+        }
+        let key = (
+            template.source.filename().to_owned(),
+            template.name.span.offset(),
+        );
+        if invalid_templates.contains(&key) {
+            continue;
+        }
+        match validator.validate_targeted_template(template, &project_file.project_root) {
+            Some(validated) => template.tasks = validated,
+            None => {
+                invalid_templates.insert(key);
+            }
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use miette::{GraphicalReportHandler, GraphicalTheme};
 
-use crate::test_files::TestFiles;
+use crate::{config::DependencyKind, test_files::TestFiles};
 
 use super::*;
 
@@ -141,6 +141,703 @@ fn test_invalid_task_path_inside_nested_task_file() {
     insta::assert_display_snapshot!(render_miette(err, &test_files));
 }
 
+#[test]
+fn test_missing_env_file_error() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "missing-env-file-project"
+
+            env_file ".env"
+
+            tasks {
+                task "test" {
+                    env_file ".env.test"
+                    command "cargo test"
+                }
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_glob_imports() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file("tasks/build.nabs", r#"task "build""#)
+        .with_file("tasks/test.nabs", r#"task "test""#)
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "glob-imports"
+
+            tasks {
+                import "/tasks/*.nabs"
+                import "/other-tasks/*.nabs"
+            }
+        "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+
+    let mut task_names = config.project_files[0]
+        .config
+        .tasks
+        .tasks
+        .iter()
+        .map(|task| task.name.as_str())
+        .collect::<Vec<_>>();
+    task_names.sort();
+    assert_eq!(task_names, vec!["build", "test"]);
+
+    let warnings = config
+        .warnings
+        .into_iter()
+        .map(|warning| render_miette(miette::Report::new(warning), &test_files))
+        .collect::<Vec<_>>();
+
+    insta::assert_display_snapshot!(warnings.join("\n"));
+}
+
+#[test]
+fn test_import_cycle_error() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file("tasks/a.nabs", r#"import "b.nabs""#)
+        .with_file("tasks/b.nabs", r#"import "c.nabs""#)
+        .with_file("tasks/c.nabs", r#"import "/tasks/a.nabs""#)
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "import-cycle"
+
+            tasks {
+                import "/tasks/a.nabs"
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_diamond_imports_are_deduplicated() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file("tasks/common.nabs", r#"task "common""#)
+        .with_file("tasks/a.nabs", "import \"common.nabs\"\ntask \"a\"")
+        .with_file("tasks/b.nabs", "import \"common.nabs\"\ntask \"b\"")
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "diamond"
+
+            tasks {
+                import "/tasks/a.nabs"
+                import "/tasks/b.nabs"
+                import "/tasks/*.nabs"
+            }
+        "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+
+    let task_names = config.project_files[0]
+        .config
+        .tasks
+        .tasks
+        .iter()
+        .map(|task| task.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(task_names, vec!["common", "a", "b"]);
+}
+
+#[test]
+fn test_namespaced_imports() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "tasks/lint.nabs",
+            r#"
+                task "fmt"
+                task "check" {
+                    requires "fmt"
+                    requires "build" in="^self"
+                }
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "namespaced"
+
+            tasks {
+                import "/tasks/lint.nabs" as="lint"
+
+                task "check" {
+                    requires "lint.check"
+                }
+            }
+        "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+    let tasks = &config.project_files[0].config.tasks.tasks;
+
+    let task_names = tasks
+        .iter()
+        .map(|task| task.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(task_names, vec!["check", "lint.fmt", "lint.check"]);
+
+    let lint_requires = tasks[2]
+        .requires
+        .iter()
+        .map(|requires| requires.task.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(lint_requires, vec!["lint.fmt", "build"]);
+}
+
+#[test]
+fn test_local_tasks_can_override_imported_tasks() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file("tasks/build.nabs", r#"task "build""#)
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "overrides"
+
+            tasks {
+                import "/tasks/build.nabs"
+
+                task "build" override=true
+            }
+        "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+    let tasks = &config.project_files[0].config.tasks.tasks;
+
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].source.filename(), "project/project.kdl");
+}
+
+#[test]
+fn test_duplicate_task_error() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file("tasks/build.nabs", r#"task "build""#)
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "duplicates"
+
+            tasks {
+                import "/tasks/build.nabs"
+
+                task "build"
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_duplicate_template_error() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "duplicates"
+
+            tasks {
+                template "t" {
+                    task "build"
+                }
+                template "t" {
+                    task "test"
+                }
+
+                use "t"
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_workspace_task_errors_are_only_reported_once() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+
+                tasks projects="projects/*" {
+                    task "lint" {
+                        command "echo {project.nmae}"
+                    }
+                }
+            "#,
+        )
+        .with_file("projects/one/project.kdl", r#"project "one""#)
+        .with_file("projects/two/project.kdl", r#"project "two""#);
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_projects_can_override_workspace_tasks() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+
+                tasks {
+                    task "lint"
+                }
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "overrides"
+
+            tasks {
+                task "lint" override=true
+            }
+        "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+    let tasks = &config.project_files[0].config.tasks.tasks;
+
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].source.filename(), "project/project.kdl");
+}
+
+#[test]
+fn test_duplicate_workspace_task_errors() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+
+                tasks {
+                    task "lint"
+                }
+
+                tasks projects="projects/*" {
+                    task "lint"
+                    task "fmt"
+                }
+            "#,
+        )
+        .with_file("projects/one/project.kdl", r#"project "one""#)
+        .with_file(
+            "projects/two/project.kdl",
+            r#"
+            project "two"
+
+            tasks {
+                task "fmt"
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_tagged_templates_from_the_workspace_file() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+
+                tasks {
+                    import "/tasks/shared.nabs"
+
+                    template "docs" {
+                        targets_tags "library"
+                        task "docs" {
+                            command "cargo doc"
+                        }
+                    }
+                }
+            "#,
+        )
+        .with_file(
+            "tasks/shared.nabs",
+            r#"
+                template "clippy" {
+                    targets_tags "rust"
+                    task "clippy" {
+                        command "cargo clippy"
+                    }
+                }
+            "#,
+        )
+        .with_file(
+            "lib/project.kdl",
+            r#"
+                project "lib"
+                tags "rust" "library"
+            "#,
+        )
+        .with_file("web/project.kdl", r#"project "web""#);
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+
+    let task_names = |name: &str| {
+        let project = config
+            .project_files
+            .iter()
+            .find(|project| project.config.project.as_str() == name)
+            .unwrap();
+        let mut names = project
+            .config
+            .tasks
+            .tasks
+            .iter()
+            .map(|task| task.name.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    assert_eq!(task_names("lib"), vec!["clippy", "docs"]);
+    assert!(task_names("web").is_empty());
+}
+
+#[test]
+fn test_tagged_template_paths_are_relative_to_each_project() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "a/project.kdl",
+            r#"
+                project "a"
+                tags "rust"
+
+                tasks {
+                    template "clippy" {
+                        targets_tags "rust"
+                        task "clippy" cwd="sub" {
+                            command "cargo clippy"
+                        }
+                    }
+                }
+            "#,
+        )
+        .with_file("a/sub/Cargo.toml", "")
+        .with_file(
+            "b/project.kdl",
+            r#"
+                project "b"
+                tags "rust"
+            "#,
+        )
+        .with_file("b/sub/Cargo.toml", "");
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+
+    for project in &config.project_files {
+        let tasks = &project.config.tasks.tasks;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(
+            tasks[0].cwd.as_ref().unwrap().as_subpath(),
+            project.project_root.as_subpath().join("sub")
+        );
+    }
+}
+
+#[test]
+fn test_unknown_excludes_warning() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+
+                tasks {
+                    task "lint" {
+                        command "cargo clippy"
+                    }
+                    task "fmt" {
+                        command "cargo fmt --check"
+                    }
+                }
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+                project "project"
+
+                tasks {
+                    exclude "lint"
+                    exclude "deploy"
+                }
+            "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+
+    let task_names = config.project_files[0]
+        .config
+        .tasks
+        .tasks
+        .iter()
+        .map(|task| task.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(task_names, vec!["fmt"]);
+
+    let warnings = config
+        .warnings
+        .into_iter()
+        .map(|warning| render_miette(miette::Report::new(warning), &test_files))
+        .collect::<Vec<_>>();
+
+    insta::assert_display_snapshot!(warnings.join("\n"));
+}
+
+#[test]
+fn test_unknown_os_error() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "conditions"
+
+            tasks {
+                task "build" {
+                    when os="osx"
+                }
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
+#[test]
+fn test_dependencies_from_manifests() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "Cargo.toml",
+            r#"
+                [workspace]
+                members = ["*"]
+
+                [workspace.dependencies]
+                lib = { path = "lib" }
+            "#,
+        )
+        .with_file("lib/project.kdl", r#"project "lib""#)
+        .with_file("lib/package.json", r#"{ "name": "@workspace/lib" }"#)
+        .with_file("fixtures/project.kdl", r#"project "fixtures""#)
+        .with_file("vendored/go.mod", "module example.com/vendored")
+        .with_file(
+            "service/project.kdl",
+            r#"
+            project "service"
+
+            dependencies {
+                from_cargo
+                from_package_json
+                from_go_mod
+            }
+        "#,
+        )
+        .with_file(
+            "service/Cargo.toml",
+            r#"
+                [package]
+                name = "service"
+
+                [dependencies]
+                lib = { workspace = true }
+
+                [dev-dependencies]
+                fixtures = { path = "../fixtures" }
+            "#,
+        )
+        .with_file(
+            "service/package.json",
+            r#"
+                {
+                    "name": "@workspace/service",
+                    "dependencies": {
+                        "@workspace/lib": "workspace:*",
+                        "@workspace/missing": "workspace:*"
+                    }
+                }
+            "#,
+        )
+        .with_file(
+            "service/go.mod",
+            r#"
+                module example.com/service
+
+                replace example.com/vendored => ../vendored
+            "#,
+        );
+
+    let config = load_config_from_path(test_files.root().into()).unwrap();
+
+    let service = config
+        .project_files
+        .iter()
+        .find(|project| project.config.project.as_str() == "service")
+        .unwrap();
+    let dependencies = service
+        .config
+        .dependencies
+        .iter()
+        .map(|dependency| (dependency.path.to_string(), dependency.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dependencies,
+        vec![
+            ("lib".to_owned(), DependencyKind::Runtime),
+            ("fixtures".to_owned(), DependencyKind::Dev),
+        ]
+    );
+
+    let warnings = config
+        .warnings
+        .into_iter()
+        .map(|warning| render_miette(miette::Report::new(warning), &test_files))
+        .collect::<Vec<_>>();
+
+    insta::assert_display_snapshot!(warnings.join("\n"));
+}
+
+#[test]
+fn test_missing_manifest_error() {
+    let test_files = TestFiles::new()
+        .with_file(
+            "workspace.kdl",
+            r#"
+                name "my-workspace"
+                project_path "**"
+            "#,
+        )
+        .with_file(
+            "project/project.kdl",
+            r#"
+            project "missing-manifest"
+
+            dependencies {
+                from_cargo
+            }
+        "#,
+        );
+
+    let err = load_config_from_path(test_files.root().into()).unwrap_err();
+
+    insta::assert_display_snapshot!(render_miette(err, &test_files));
+}
+
 fn render_miette(e: miette::Report, test_files: &TestFiles) -> String {
     let mut report = String::new();
     GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
@@ -1,5 +1,7 @@
 mod config_source;
+mod dependency_check;
 mod glob;
+mod interpolation;
 mod loader;
 mod parsing;
 mod paths;
@@ -9,12 +11,19 @@ mod validated;
 use self::paths::ConfigPath;
 pub use self::{
     config_source::ConfigSource,
+    dependency_check::check_dependencies,
     glob::Glob,
+    interpolation::{InterpolatedString, InterpolationContext},
     loader::{load_config_from_path, load_project_files},
     parsing::{ParsingError, Validator},
     paths::{ValidPath, WorkspaceRoot},
-    validated::{project::ProjectDefinition, tasks::*, workspace::WorkspaceDefinition},
+    validated::{
+        project::{DependencyKind, ProjectDefinition},
+        tasks::*,
+        workspace::{DependencyRule, ProjectMatcher, WorkspaceDefinition},
+    },
 };
+use crate::diagnostics::DynDiagnostic;
 
 #[cfg(test)]
 mod tests;
@@ -39,6 +48,7 @@ struct ConfigValidationError(#[related] Vec<miette::Report>);
 pub struct ValidConfig {
     pub workspace_file: WorkspaceFile,
     pub project_files: Vec<ValidProjectFile>,
+    pub warnings: Vec<DynDiagnostic>,
 }
 
 #[derive(Debug)]
@@ -64,7 +74,11 @@ pub struct UnvalidatedProjectFile {
 
 impl UnvalidatedProjectFile {
     pub fn unvalidated_dependency_paths(&self) -> impl Iterator<Item = &ConfigPath> {
-        self.config.dependencies.projects.iter().map(|p| p.as_ref())
+        self.config
+            .dependencies
+            .iter()
+            .flat_map(|block| &block.projects)
+            .map(|dependency| dependency.path.as_ref())
     }
 }
 
@@ -0,0 +1,442 @@
+//! Reads dependencies on other projects out of language specific manifests
+//! like Cargo.toml & package.json
+
+use std::collections::{HashMap, HashSet};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::super::{
+    paths::{ValidPath, WorkspaceRoot},
+    validated::{DependencyKind, Manifest},
+};
+
+/// A dependency found in a manifest that may refer to another project
+#[derive(Debug, PartialEq, Eq)]
+pub struct ManifestDependency {
+    /// The dependency as it was written in the manifest, for reporting
+    pub declared: String,
+    pub target: DependencyTarget,
+    pub kind: DependencyKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DependencyTarget {
+    /// A dependency on whatever lives at this path
+    Path(Utf8PathBuf),
+    /// A dependency on a package in the same JS workspace, which can only be
+    /// found by looking at the names in other projects package.json
+    Package(String),
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum ManifestError {
+    #[error("Couldn't read {path}")]
+    Io {
+        path: Utf8PathBuf,
+        #[source]
+        error: std::io::Error,
+        #[label("dependencies are read from this manifest")]
+        span: miette::SourceSpan,
+    },
+    #[error("Couldn't parse {path}: {message}")]
+    Invalid {
+        path: Utf8PathBuf,
+        message: String,
+        #[label("dependencies are read from this manifest")]
+        span: miette::SourceSpan,
+    },
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{manifest} depends on {dependency}, which isn't a project in this workspace")]
+#[diagnostic(
+    severity(Warning),
+    help("only dependencies on directories with a project.kdl are added to the project")
+)]
+pub struct ManifestDependencyWarning {
+    pub(super) manifest: &'static str,
+    pub(super) dependency: String,
+    #[label("this dependency was read from {manifest}")]
+    pub(super) span: miette::SourceSpan,
+}
+
+/// Reads any dependencies that might be on other projects from one of a projects manifests
+pub fn read_dependencies(
+    manifest: Manifest,
+    project_root: &ValidPath,
+    workspace_root: &WorkspaceRoot,
+    span: miette::SourceSpan,
+) -> Result<Vec<ManifestDependency>, ManifestError> {
+    let project_dir = project_root.full_path();
+    let path = project_root.as_subpath().join(manifest.file_name());
+    let contents =
+        std::fs::read_to_string(project_dir.join(manifest.file_name())).map_err(|error| {
+            ManifestError::Io {
+                path: path.clone(),
+                error,
+                span,
+            }
+        })?;
+    let invalid = |message: String| ManifestError::Invalid {
+        path: path.clone(),
+        message,
+        span,
+    };
+
+    match manifest {
+        Manifest::Cargo => {
+            let cargo_workspace = find_cargo_workspace(&project_dir, workspace_root.as_ref());
+            let cargo_workspace = cargo_workspace.as_ref().map(|(dir, manifest)| {
+                let dependencies = manifest
+                    .get("workspace")
+                    .and_then(|workspace| workspace.get("dependencies"));
+                (dir.as_path(), dependencies)
+            });
+            cargo_dependencies(&contents, &project_dir, cargo_workspace)
+                .map_err(|e| invalid(e.to_string()))
+        }
+        Manifest::PackageJson => {
+            package_json_dependencies(&contents, &project_dir).map_err(|e| invalid(e.to_string()))
+        }
+        Manifest::GoMod => Ok(go_mod_dependencies(&contents, &project_dir)),
+    }
+}
+
+/// Works out which projects (if any) manifest dependencies point at
+pub struct ManifestResolver {
+    workspace_root: WorkspaceRoot,
+    project_roots: HashSet<ValidPath>,
+    /// The package.json names of each project, loaded the first time they're needed
+    package_names: Option<HashMap<String, ValidPath>>,
+}
+
+impl ManifestResolver {
+    pub fn new(
+        workspace_root: WorkspaceRoot,
+        project_roots: impl IntoIterator<Item = ValidPath>,
+    ) -> Self {
+        ManifestResolver {
+            workspace_root,
+            project_roots: project_roots.into_iter().collect(),
+            package_names: None,
+        }
+    }
+
+    /// The root of the project a dependency points at
+    pub fn resolve(&mut self, target: DependencyTarget) -> Option<ValidPath> {
+        match target {
+            DependencyTarget::Path(path) => self
+                .workspace_root
+                .normalise_absolute(path)
+                .ok()
+                .filter(|path| self.project_roots.contains(path)),
+            DependencyTarget::Package(name) => {
+                let project_roots = &self.project_roots;
+                self.package_names
+                    .get_or_insert_with(|| {
+                        project_roots
+                            .iter()
+                            .filter_map(|root| Some((package_name(root)?, root.clone())))
+                            .collect()
+                    })
+                    .get(&name)
+                    .cloned()
+            }
+        }
+    }
+}
+
+/// The name from the package.json in a project, if it has one
+fn package_name(project_root: &ValidPath) -> Option<String> {
+    let contents = std::fs::read_to_string(project_root.full_path().join("package.json")).ok()?;
+    let package = serde_json::from_str::<serde_json::Value>(&contents).ok()?;
+    Some(package.get("name")?.as_str()?.to_owned())
+}
+
+const CARGO_DEPENDENCY_TABLES: [(&str, DependencyKind); 3] = [
+    ("dependencies", DependencyKind::Runtime),
+    ("build-dependencies", DependencyKind::Build),
+    ("dev-dependencies", DependencyKind::Dev),
+];
+
+fn cargo_dependencies(
+    contents: &str,
+    manifest_dir: &Utf8Path,
+    workspace: Option<(&Utf8Path, Option<&toml::Value>)>,
+) -> Result<Vec<ManifestDependency>, toml::de::Error> {
+    let manifest = toml::from_str::<toml::Value>(contents)?;
+
+    let targets = manifest
+        .get("target")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|targets| targets.values());
+
+    let mut dependencies = Vec::new();
+    for tables in std::iter::once(&manifest).chain(targets) {
+        for (table_name, kind) in CARGO_DEPENDENCY_TABLES {
+            let Some(table) = tables.get(table_name).and_then(toml::Value::as_table) else {
+                continue;
+            };
+            for (name, dependency) in table {
+                let inherited = dependency
+                    .get("workspace")
+                    .and_then(toml::Value::as_bool)
+                    .unwrap_or_default();
+
+                let (dir, dependency) = match (inherited, workspace) {
+                    (false, _) => (manifest_dir, dependency),
+                    (true, Some((dir, Some(workspace_dependencies)))) => {
+                        let Some(dependency) = workspace_dependencies.get(name) else {
+                            continue;
+                        };
+                        (dir, dependency)
+                    }
+                    (true, _) => continue,
+                };
+
+                if let Some(path) = dependency.get("path").and_then(toml::Value::as_str) {
+                    dependencies.push(ManifestDependency {
+                        declared: path.to_owned(),
+                        target: DependencyTarget::Path(dir.join(path)),
+                        kind,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(dependencies)
+}
+
+/// Finds the Cargo.toml that defines the cargo workspace a crate belongs to.
+///
+/// This doesn't look any further up than the root of our own workspace.
+fn find_cargo_workspace(
+    manifest_dir: &Utf8Path,
+    workspace_root: &Utf8Path,
+) -> Option<(Utf8PathBuf, toml::Value)> {
+    for dir in manifest_dir.ancestors() {
+        if !dir.starts_with(workspace_root) {
+            break;
+        }
+        let Ok(contents) = std::fs::read_to_string(dir.join("Cargo.toml")) else {
+            continue;
+        };
+        let Ok(manifest) = toml::from_str::<toml::Value>(&contents) else {
+            continue;
+        };
+        if manifest.get("workspace").is_some() {
+            return Some((dir.to_owned(), manifest));
+        }
+    }
+    None
+}
+
+const PACKAGE_JSON_DEPENDENCY_FIELDS: [(&str, DependencyKind); 4] = [
+    ("dependencies", DependencyKind::Runtime),
+    ("optionalDependencies", DependencyKind::Runtime),
+    ("peerDependencies", DependencyKind::Runtime),
+    ("devDependencies", DependencyKind::Dev),
+];
+
+fn package_json_dependencies(
+    contents: &str,
+    manifest_dir: &Utf8Path,
+) -> Result<Vec<ManifestDependency>, serde_json::Error> {
+    let package = serde_json::from_str::<serde_json::Value>(contents)?;
+
+    let mut dependencies = Vec::new();
+    for (field, kind) in PACKAGE_JSON_DEPENDENCY_FIELDS {
+        let Some(field) = package.get(field).and_then(serde_json::Value::as_object) else {
+            continue;
+        };
+        for (name, specifier) in field {
+            let Some(specifier) = specifier.as_str() else {
+                continue;
+            };
+
+            let path = ["file:", "link:", "portal:"]
+                .into_iter()
+                .find_map(|protocol| specifier.strip_prefix(protocol));
+
+            let target = if let Some(path) = path {
+                DependencyTarget::Path(manifest_dir.join(path))
+            } else if specifier.starts_with("workspace:") {
+                DependencyTarget::Package(name.clone())
+            } else {
+                continue;
+            };
+
+            dependencies.push(ManifestDependency {
+                declared: format!("{name}@{specifier}"),
+                target,
+                kind,
+            });
+        }
+    }
+
+    Ok(dependencies)
+}
+
+/// Finds the local directories that a go.mod replaces modules with.
+///
+/// Go modules can only refer to other modules in the same repository via
+/// `replace` directives, so those are all we look at.
+fn go_mod_dependencies(contents: &str, manifest_dir: &Utf8Path) -> Vec<ManifestDependency> {
+    let mut dependencies = Vec::new();
+    let mut in_replace_block = false;
+
+    for line in contents.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+
+        let replace = if in_replace_block {
+            if line == ")" {
+                in_replace_block = false;
+                continue;
+            }
+            line
+        } else if let Some(rest) = line.strip_prefix("replace") {
+            let rest = rest.trim();
+            if rest == "(" {
+                in_replace_block = true;
+                continue;
+            }
+            rest
+        } else {
+            continue;
+        };
+
+        let Some((_, replacement)) = replace.split_once("=>") else {
+            continue;
+        };
+        let Some(path) = replacement.split_whitespace().next() else {
+            continue;
+        };
+        if path.starts_with("./") || path.starts_with("../") {
+            dependencies.push(ManifestDependency {
+                declared: path.to_owned(),
+                target: DependencyTarget::Path(manifest_dir.join(path)),
+                kind: DependencyKind::Runtime,
+            });
+        }
+    }
+
+    dependencies
+}
+
+#[cfg(test)]
+mod tests {
+    use similar_asserts::assert_eq;
+
+    use super::*;
+
+    fn path_dependency(path: &str, kind: DependencyKind) -> ManifestDependency {
+        ManifestDependency {
+            declared: path.to_owned(),
+            target: DependencyTarget::Path(Utf8Path::new("/repo/service").join(path)),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_cargo_dependencies() {
+        let manifest = r#"
+            [package]
+            name = "service"
+
+            [dependencies]
+            serde = "1.0"
+            lib = { path = "../lib" }
+            shared = { workspace = true }
+
+            [dev-dependencies]
+            fixtures = { path = "../fixtures" }
+
+            [target.'cfg(unix)'.build-dependencies]
+            codegen = { path = "../codegen" }
+        "#;
+        let workspace_dependencies = toml::toml! {
+            shared = { path = "shared" }
+        };
+
+        assert_eq!(
+            cargo_dependencies(
+                manifest,
+                Utf8Path::new("/repo/service"),
+                Some((Utf8Path::new("/repo"), Some(&workspace_dependencies)))
+            )
+            .unwrap(),
+            vec![
+                path_dependency("../lib", DependencyKind::Runtime),
+                ManifestDependency {
+                    declared: "shared".to_owned(),
+                    target: DependencyTarget::Path("/repo/shared".into()),
+                    kind: DependencyKind::Runtime,
+                },
+                path_dependency("../fixtures", DependencyKind::Dev),
+                path_dependency("../codegen", DependencyKind::Build),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_package_json_dependencies() {
+        let manifest = r#"{
+            "name": "service",
+            "dependencies": {
+                "react": "^18.0.0",
+                "lib": "file:../lib",
+                "shared": "workspace:*"
+            },
+            "devDependencies": {
+                "fixtures": "link:../fixtures"
+            }
+        }"#;
+
+        assert_eq!(
+            package_json_dependencies(manifest, Utf8Path::new("/repo/service")).unwrap(),
+            vec![
+                ManifestDependency {
+                    declared: "lib@file:../lib".to_owned(),
+                    target: DependencyTarget::Path("/repo/service/../lib".into()),
+                    kind: DependencyKind::Runtime,
+                },
+                ManifestDependency {
+                    declared: "shared@workspace:*".to_owned(),
+                    target: DependencyTarget::Package("shared".to_owned()),
+                    kind: DependencyKind::Runtime,
+                },
+                ManifestDependency {
+                    declared: "fixtures@link:../fixtures".to_owned(),
+                    target: DependencyTarget::Path("/repo/service/../fixtures".into()),
+                    kind: DependencyKind::Dev,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_go_mod_dependencies() {
+        let manifest = r#"
+            module example.com/service
+
+            require example.com/lib v0.0.0
+
+            replace example.com/lib => ../lib // a local checkout
+            replace example.com/fork v1.0.0 => github.com/someone/fork v1.0.1
+
+            replace (
+                example.com/shared => ./shared
+            )
+        "#;
+
+        assert_eq!(
+            go_mod_dependencies(manifest, Utf8Path::new("/repo/service")),
+            vec![
+                path_dependency("../lib", DependencyKind::Runtime),
+                path_dependency("./shared", DependencyKind::Runtime),
+            ]
+        );
+    }
+}
@@ -1,11 +1,19 @@
 use camino::{Utf8Path, Utf8PathBuf};
 
+mod manifests;
+mod names;
 mod project;
 mod tasks;
 mod validation;
 mod workspace;
 
-pub(super) use self::{project::ProjectDefinition, tasks::*, workspace::WorkspaceDefinition};
+pub(super) use self::{
+    manifests::{read_dependencies, ManifestDependencyWarning, ManifestResolver},
+    names::{validate_name, NameKind},
+    project::{DuplicateProjectError, ProjectDefinedHere, ProjectDefinition},
+    tasks::*,
+    workspace::{WorkspaceDefinition, WorkspaceTasks},
+};
 
 pub use validation::Validator;
 
@@ -54,9 +62,6 @@ pub fn parse_project_file(config: &ConfigSource) -> Result<ProjectDefinition, Pa
     let config = knuffel::parse::<ProjectDefinition>(filename, config.contents())
         .map_err(|e| ParsingError(e, filename.to_owned().into()))?;
 
-    // TODO: At some point want to validate the data in the file.
-    // e.g. names can't have commas or slashes in them etc.
-
     Ok(config)
 }
 
@@ -74,9 +79,6 @@ pub fn parse_workspace_file(
         ParsingError(e, filename)
     })?;
 
-    // TODO: At some point want to validate the data in the file.
-    // e.g. names can't have commas or slashes in them etc.
-
     Ok(config)
 }
 
@@ -88,9 +90,5 @@ pub fn parse_task_file(path: &Utf8Path, contents: &str) -> Result<TaskBlock, Par
     )
     .map_err(|e| ParsingError(e, path.to_owned()))?;
 
-    // TODO: At some point want to validate the data in the file.
-    // e.g. names can't have commas or slashes in them etc.
-
     Ok(config)
 }
-
@@ -0,0 +1,73 @@
+use std::fmt;
+
+use crate::config::spanned::Spanned;
+
+/// Characters that can't appear in project or task names.
+///
+/// Slashes and colons would make task references like `projects/a-lib::build`
+/// ambiguous, and commas would do the same for lists of names on the command line.
+/// Square brackets are reserved for the instances of matrix tasks, e.g. `build[target=wasm]`.
+const ILLEGAL_CHARACTERS: &[char] = &[',', '/', ':', '[', ']'];
+
+#[derive(Clone, Copy, Debug)]
+pub enum NameKind {
+    Project,
+    Task,
+    MatrixVariable,
+    MatrixValue,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum NameError {
+    #[error("The {kind} {name:?} contains an illegal character")]
+    #[diagnostic(help(
+        "names can't contain commas, slashes, colons, square brackets or whitespace"
+    ))]
+    IllegalCharacter {
+        kind: NameKind,
+        name: String,
+        #[label("{character:?} isn't allowed here")]
+        span: miette::SourceSpan,
+        character: char,
+    },
+    #[error("A {kind} can't be empty")]
+    Empty {
+        kind: NameKind,
+        #[label("this name is empty")]
+        span: miette::SourceSpan,
+    },
+}
+
+pub fn validate_name(kind: NameKind, name: &Spanned<String>) -> Result<(), NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty {
+            kind,
+            span: name.span,
+        });
+    }
+
+    let illegal = name
+        .chars()
+        .find(|c| ILLEGAL_CHARACTERS.contains(c) || c.is_whitespace());
+    if let Some(character) = illegal {
+        return Err(NameError::IllegalCharacter {
+            kind,
+            name: name.to_string(),
+            span: name.span,
+            character,
+        });
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for NameKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameKind::Project => write!(f, "project name"),
+            NameKind::Task => write!(f, "task name"),
+            NameKind::MatrixVariable => write!(f, "matrix variable name"),
+            NameKind::MatrixValue => write!(f, "matrix value"),
+        }
+    }
+}
@@ -1,21 +1,81 @@
-use crate::config::spanned::Spanned;
+use crate::{
+    config::spanned::{NodeSpan, Spanned},
+    diagnostics::DynDiagnostic,
+};
 
-use super::{super::paths::ConfigPath, tasks};
+use super::{
+    super::{paths::ConfigPath, validated::DependencyKind},
+    tasks,
+};
 
 #[derive(knuffel::Decode, Debug)]
 pub struct ProjectDefinition {
     #[knuffel(child, unwrap(argument))]
-    pub(super) project: String,
+    pub(super) project: Spanned<String>,
 
-    #[knuffel(child, default)]
-    pub(in crate::config) dependencies: DependencyBlock,
+    #[knuffel(child, unwrap(arguments), default)]
+    pub(super) tags: Vec<String>,
+
+    #[knuffel(child)]
+    pub(in crate::config) dependencies: Option<DependencyBlock>,
+
+    #[knuffel(children(name = "env_file"), unwrap(argument))]
+    pub(super) env_files: Vec<ConfigPath>,
 
     #[knuffel(child, default)]
     pub(super) tasks: tasks::TaskBlock,
 }
 
-#[derive(knuffel::Decode, Debug, Default)]
+#[derive(knuffel::Decode, Debug)]
 pub struct DependencyBlock {
-    #[knuffel(children(name = "project"), unwrap(argument))]
-    pub(in crate::config) projects: Vec<Spanned<ConfigPath>>,
+    #[knuffel(span)]
+    pub(super) span: NodeSpan,
+
+    #[knuffel(children(name = "project"))]
+    pub(in crate::config) projects: Vec<ProjectDependency>,
+
+    #[knuffel(children(name = "from_cargo"))]
+    pub(super) from_cargo: Vec<ManifestImport>,
+
+    #[knuffel(children(name = "from_package_json"))]
+    pub(super) from_package_json: Vec<ManifestImport>,
+
+    #[knuffel(children(name = "from_go_mod"))]
+    pub(super) from_go_mod: Vec<ManifestImport>,
+}
+
+/// A request to read dependencies from one of the projects language specific manifests
+#[derive(knuffel::Decode, Debug)]
+pub struct ManifestImport {
+    #[knuffel(span)]
+    pub(super) span: NodeSpan,
+}
+
+#[derive(knuffel::Decode, Debug)]
+pub struct ProjectDependency {
+    #[knuffel(span)]
+    pub(in crate::config) span: NodeSpan,
+
+    #[knuffel(argument)]
+    pub(in crate::config) path: Spanned<ConfigPath>,
+
+    #[knuffel(property, default)]
+    pub(in crate::config) kind: DependencyKind,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("There's more than one project named {name}")]
+#[diagnostic(help("project names need to be unique across the workspace"))]
+pub struct DuplicateProjectError {
+    pub(super) name: String,
+    #[related]
+    pub(super) definitions: Vec<DynDiagnostic>,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{name} is defined here")]
+pub struct ProjectDefinedHere {
+    pub(super) name: String,
+    #[label("this project is named {name}")]
+    pub(super) span: miette::SourceSpan,
 }
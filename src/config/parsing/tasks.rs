@@ -1,20 +1,70 @@
-use validated::{SpecificProjectSelector, TargetSelector};
+use std::collections::BTreeMap;
 
-use crate::config::{
-    paths::{ConfigPath, ConfigPathValidationError},
-    spanned::{SourceSpanExt, Spanned, WithSpan},
-    validated, Glob, WorkspaceRoot,
+use validated::{SpecificProjectSelector, TargetSelector, KNOWN_OSES};
+
+use crate::{
+    config::{
+        interpolation::{InterpolatedString, InterpolationError},
+        parsing::names::{validate_name, NameError, NameKind},
+        paths::{ConfigPath, ConfigPathValidationError},
+        spanned::{SourceSpanExt, Spanned, WithSpan},
+        validated, WorkspaceRoot,
+    },
+    diagnostics::{CollectResults, DynDiagnostic},
 };
 
 #[derive(knuffel::Decode, Debug, Default)]
 pub struct TaskBlock {
-    #[knuffel(children(name = "import"), unwrap(argument))]
-    pub(in crate::config) imports: Vec<ConfigPath>,
+    #[knuffel(children(name = "exclude"), unwrap(argument))]
+    pub(in crate::config) excludes: Vec<Spanned<String>>,
+
+    #[knuffel(children(name = "import"))]
+    pub(in crate::config) imports: Vec<TaskImport>,
+
+    #[knuffel(children(name = "template"))]
+    pub(in crate::config) templates: Vec<TemplateDefinition>,
+
+    #[knuffel(children(name = "use"))]
+    pub(in crate::config) uses: Vec<TemplateUse>,
 
     #[knuffel(children(name = "task"))]
     pub(in crate::config) tasks: Vec<TaskDefinition>,
 }
 
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct TaskImport {
+    #[knuffel(argument)]
+    pub(super) path: ConfigPath,
+
+    /// A namespace to prefix the names of the imported tasks with
+    #[knuffel(property(name = "as"))]
+    pub(super) namespace: Option<String>,
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct TemplateDefinition {
+    #[knuffel(argument)]
+    pub(super) name: Spanned<String>,
+
+    #[knuffel(children(name = "variable"), unwrap(argument))]
+    pub(super) variables: Vec<Spanned<String>>,
+
+    #[knuffel(children(name = "targets_tags"), unwrap(arguments))]
+    pub(super) targets_tags: Vec<Vec<String>>,
+
+    #[knuffel(children(name = "task"))]
+    pub(super) tasks: Vec<TaskDefinition>,
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct TemplateUse {
+    #[knuffel(argument)]
+    pub(super) template: Spanned<String>,
+
+    #[knuffel(properties)]
+    pub(super) variables: BTreeMap<String, Spanned<String>>,
+}
+
 #[derive(thiserror::Error, miette::Diagnostic, Debug)]
 pub enum TaskValidationError {
     #[error("Invalid paths in a task file")]
@@ -25,40 +75,358 @@ pub enum TaskValidationError {
         span: miette::SourceSpan,
         message: String,
     },
+    #[error("A when condition needs an os or env property")]
+    #[diagnostic(help("for example `when os=\"linux\"` or `when env=\"CI\"`"))]
+    EmptyCondition {
+        #[label("this condition doesn't check anything")]
+        span: miette::SourceSpan,
+    },
+    #[error("Unknown operating system {os}")]
+    #[diagnostic(help("os should be one of {}", KNOWN_OSES.join(", ")))]
+    UnknownOs {
+        os: String,
+        #[label("this condition would never hold")]
+        span: miette::SourceSpan,
+    },
+    #[error("The matrix variable {name} doesn't have any values")]
+    #[diagnostic(help("list the values after the name, e.g. `matrix \"{name}\" \"a\" \"b\"`"))]
+    EmptyMatrix {
+        name: String,
+        #[label("this needs at least one value")]
+        span: miette::SourceSpan,
+    },
+    #[error("The matrix variable {name} is declared more than once")]
+    DuplicateMatrixVariable {
+        name: String,
+        #[label("{name} is already declared on this task")]
+        span: miette::SourceSpan,
+    },
+    #[error("Undeclared matrix variable {{matrix.{name}}}")]
+    #[diagnostic(help(
+        "declare the variable on the task with `matrix \"{name}\"` and its values"
+    ))]
+    UndeclaredMatrixVariable {
+        name: String,
+        #[label("this string references {{matrix.{name}}}")]
+        span: miette::SourceSpan,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    InvalidMatrixName(NameError),
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("The import {pattern} didn't match any files")]
+#[diagnostic(severity(Warning))]
+pub struct EmptyImportWarning {
+    pub(super) pattern: String,
+    #[label("this import matched nothing")]
+    pub(super) span: miette::SourceSpan,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("There's no workspace task named {name} to exclude")]
+#[diagnostic(
+    severity(Warning),
+    help("exclude only applies to tasks added to this project by the workspace file")
+)]
+pub struct UnknownExcludeWarning {
+    pub(super) name: String,
+    #[label("this exclude has no effect")]
+    pub(super) span: miette::SourceSpan,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum TaskNameError {
+    #[error("The task {name} is defined more than once")]
+    #[diagnostic(help(
+        "use `as` on an import to put its tasks in a namespace, or add override=true to the task that should replace the others"
+    ))]
+    Duplicate {
+        name: String,
+        #[related]
+        definitions: Vec<DynDiagnostic>,
+    },
+    #[error("More than one definition of the task {name} has override=true")]
+    #[diagnostic(help("only one task can override the others"))]
+    MultipleOverrides {
+        name: String,
+        #[related]
+        definitions: Vec<DynDiagnostic>,
+    },
 }
 
-#[derive(knuffel::Decode, Debug)]
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{name} is defined here")]
+pub struct TaskDefinedHere {
+    pub(super) name: String,
+    #[label("this defines {name}")]
+    pub(super) span: miette::SourceSpan,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("The template {name} is defined here")]
+pub struct TemplateDefinedHere {
+    pub(super) name: String,
+    #[label("this defines {name}")]
+    pub(super) span: miette::SourceSpan,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum TemplateError {
+    #[error("Couldn't find a template named {name}")]
+    #[diagnostic(help("templates need to be defined in this project or in a file it imports"))]
+    UnknownTemplate {
+        name: String,
+        #[label("this template doesn't exist")]
+        span: miette::SourceSpan,
+    },
+    #[error("The template {name} is defined more than once")]
+    #[diagnostic(help("rename one of these templates"))]
+    DuplicateTemplate {
+        name: String,
+        #[related]
+        definitions: Vec<DynDiagnostic>,
+    },
+    #[error("Missing variables for template {name}: {}", missing.join(", "))]
+    MissingVariables {
+        name: String,
+        missing: Vec<String>,
+        #[label("this use needs values for {}", missing.join(", "))]
+        span: miette::SourceSpan,
+    },
+    #[error("The template {template} doesn't have a variable named {name}")]
+    UnknownVariable {
+        template: String,
+        name: String,
+        #[label("this is passed as {name}")]
+        span: miette::SourceSpan,
+    },
+    #[error("Undeclared template variable {{var.{name}}}")]
+    #[diagnostic(help("declare the variable in the template with `variable \"{name}\"`"))]
+    UndeclaredVariable {
+        name: String,
+        #[label("this string references {{var.{name}}}")]
+        span: miette::SourceSpan,
+    },
+    #[error("The template {name} targets tags but also declares variables")]
+    #[diagnostic(help(
+        "templates applied via targets_tags can't be given variables, so can't declare any"
+    ))]
+    TargetedTemplateWithVariables {
+        name: String,
+        #[label("this template is applied to tagged projects automatically")]
+        span: miette::SourceSpan,
+    },
+    #[error("Template variable {{var.{name}}} used outside of a template")]
+    VariableOutsideTemplate {
+        name: String,
+        #[label("template variables can only be used in a template")]
+        span: miette::SourceSpan,
+    },
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
 pub struct TaskDefinition {
     #[knuffel(argument)]
-    pub(super) name: String,
+    pub(super) name: Spanned<String>,
 
-    #[knuffel(children(name = "command"), unwrap(argument))]
-    pub(super) commands: Vec<String>,
+    /// Whether this task should replace any other tasks with the same name
+    #[knuffel(property(name = "override"), default)]
+    pub(super) overrides: bool,
+
+    #[knuffel(child, unwrap(argument))]
+    pub(super) description: Option<String>,
+
+    #[knuffel(property)]
+    pub(super) cwd: Option<ConfigPath>,
+
+    #[knuffel(children(name = "env"))]
+    pub(super) env: Vec<EnvVar>,
+
+    #[knuffel(children(name = "env_file"), unwrap(argument))]
+    pub(super) env_files: Vec<ConfigPath>,
+
+    #[knuffel(children(name = "command"))]
+    pub(super) commands: Vec<TaskCommand>,
 
     #[knuffel(children(name = "requires"))]
     pub(super) requires: Vec<TaskRequires>,
 
+    /// Tasks this one should run after if they're also being run, without
+    /// requiring them to run.
+    #[knuffel(children(name = "after"))]
+    pub(super) after: Vec<TaskRequires>,
+
     #[knuffel(children(name = "inputs"))]
     pub(super) input_blocks: Vec<InputBlock>,
+
+    #[knuffel(children(name = "when"))]
+    pub(super) conditions: Vec<validated::Condition>,
+
+    #[knuffel(children(name = "matrix"))]
+    pub(super) matrix: Vec<validated::MatrixVariable>,
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct TaskCommand {
+    #[knuffel(argument)]
+    command: Spanned<String>,
+
+    #[knuffel(children(name = "when"))]
+    conditions: Vec<validated::Condition>,
+}
+
+impl TaskCommand {
+    pub fn parse(self) -> Result<validated::TaskCommand, Vec<InterpolationError>> {
+        Ok(validated::TaskCommand {
+            command: InterpolatedString::parse(self.command)?,
+            conditions: self.conditions,
+        })
+    }
 }
 
-impl From<InputBlock> for validated::InputBlock {
-    fn from(value: InputBlock) -> Self {
-        validated::InputBlock {
-            paths: value.paths,
-            env_vars: value.env_vars,
-            commands: value.commands,
+impl TaskDefinition {
+    /// Checks that every `when` in this task actually has something to check
+    pub(super) fn check_conditions(&self) -> Result<(), Vec<TaskValidationError>> {
+        let conditions = self
+            .conditions
+            .iter()
+            .chain(self.commands.iter().flat_map(|c| &c.conditions))
+            .chain(self.requires.iter().flat_map(|r| &r.conditions))
+            .chain(self.after.iter().flat_map(|r| &r.conditions))
+            .chain(self.input_blocks.iter().flat_map(|b| &b.conditions));
+
+        let mut errors = Vec::new();
+        for condition in conditions {
+            if condition.os.is_none() && condition.env.is_none() {
+                errors.push(TaskValidationError::EmptyCondition {
+                    span: condition.span.0,
+                });
+            }
+            if let Some(os) = &condition.os {
+                if !KNOWN_OSES.contains(&os.as_str()) {
+                    errors.push(TaskValidationError::UnknownOs {
+                        os: os.to_string(),
+                        span: os.span,
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
 }
 
-#[derive(knuffel::Decode, Debug)]
+impl TaskDefinition {
+    /// Checks that every matrix variable on this task is declared once and has some values,
+    /// and that the variables and values can safely go in the names of the tasks instances
+    pub(super) fn check_matrix(&self) -> Result<(), Vec<TaskValidationError>> {
+        let mut errors = Vec::new();
+        for (index, variable) in self.matrix.iter().enumerate() {
+            errors.extend(
+                validate_name(NameKind::MatrixVariable, &variable.name)
+                    .err()
+                    .map(TaskValidationError::InvalidMatrixName),
+            );
+            errors.extend(
+                variable
+                    .values
+                    .iter()
+                    .filter_map(|value| validate_name(NameKind::MatrixValue, value).err())
+                    .map(TaskValidationError::InvalidMatrixName),
+            );
+            if variable.values.is_empty() {
+                errors.push(TaskValidationError::EmptyMatrix {
+                    name: variable.name.to_string(),
+                    span: variable.name.span,
+                });
+            }
+            if self.matrix[..index]
+                .iter()
+                .any(|other| other.name.as_str() == variable.name.as_str())
+            {
+                errors.push(TaskValidationError::DuplicateMatrixVariable {
+                    name: variable.name.to_string(),
+                    span: variable.name.span,
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl InputBlock {
+    pub fn parse(self) -> Result<validated::InputBlock, Vec<InterpolationError>> {
+        let paths = parse_interpolated(self.paths);
+        let env_vars = parse_interpolated(self.env_vars);
+        let commands = parse_interpolated(self.commands);
+
+        let (paths, env_vars, commands) = match (paths, env_vars, commands) {
+            (Ok(paths), Ok(env_vars), Ok(commands)) => (paths, env_vars, commands),
+            (paths, env_vars, commands) => {
+                return Err([paths.err(), env_vars.err(), commands.err()]
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .collect())
+            }
+        };
+
+        Ok(validated::InputBlock {
+            paths,
+            env_vars,
+            commands,
+            conditions: self.conditions,
+        })
+    }
+}
+
+pub(super) fn parse_interpolated(
+    strings: Vec<Spanned<String>>,
+) -> Result<Vec<InterpolatedString>, Vec<InterpolationError>> {
+    let results = strings
+        .into_iter()
+        .map(InterpolatedString::parse)
+        .collect_results();
+
+    results.map_err(|errors| errors.into_iter().flatten().collect())
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct EnvVar {
+    #[knuffel(argument)]
+    pub(super) name: String,
+
+    #[knuffel(argument)]
+    pub(super) value: Spanned<String>,
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
 pub struct TaskRequires {
     #[knuffel(argument)]
     task: Spanned<String>,
 
     #[knuffel(property(name = "in"))]
     target: Option<Spanned<String>>,
+
+    #[knuffel(property, default)]
+    optional: bool,
+
+    #[knuffel(property(name = "include_dev"), default = true)]
+    include_dev: bool,
+
+    #[knuffel(children(name = "when"))]
+    conditions: Vec<validated::Condition>,
 }
 
 impl TaskRequires {
@@ -67,13 +435,18 @@ impl TaskRequires {
         workspace_root: &WorkspaceRoot,
     ) -> Result<validated::TaskRequires, TaskValidationError> {
         use chumsky::Parser;
-        use target_selector::ParsedSelector;
 
         let task_span = self.task.span;
         let task = self.task.into_inner().with_span(task_span);
 
         if self.target.is_none() {
-            return Ok(validated::TaskRequires { task, target: None });
+            return Ok(validated::TaskRequires {
+                task,
+                target: None,
+                optional: self.optional,
+                include_dev: self.include_dev,
+                conditions: self.conditions,
+            });
         }
 
         let target = self.target.unwrap();
@@ -92,48 +465,84 @@ impl TaskRequires {
                 }
             })?;
 
-        let target = match target {
-            ParsedSelector::CurrentProject => TargetSelector::CurrentProject,
-            ParsedSelector::DependenciesOfCurrent => TargetSelector::DependenciesOfCurrent,
-            ParsedSelector::ProjectByName(name, span) => TargetSelector::SpecificDependency(
-                SpecificProjectSelector::ByName(name)
-                    .with_span(target_span.subspan(span.start + 1, span.len())),
-            ),
-            ParsedSelector::ProjectByPath(path, span) => {
-                let span = target_span.subspan(span.start + 1, span.len());
-                TargetSelector::SpecificDependency(
-                    SpecificProjectSelector::ByPath(
-                        workspace_root
-                            .subpath(path)
-                            .and_then(|p| p.validate())
-                            .map_err(|e| {
-                                TaskValidationError::InvalidPaths(vec![
-                                    ConfigPathValidationError::new(e, span),
-                                ])
-                            })?,
-                    )
-                    .with_span(span),
-                )
-            }
-        };
+        let target = validate_selector(target, target_span, workspace_root)?;
 
         Ok(validated::TaskRequires {
             task,
             target: Some(target.with_span(target_span)),
+            optional: self.optional,
+            include_dev: self.include_dev,
+            conditions: self.conditions,
         })
     }
 }
 
-#[derive(knuffel::Decode, Debug)]
+/// Converts a parsed selector into a validated one.
+///
+/// `target_span` is the span of the whole `in` property, which the spans from the
+/// parser are relative to.
+fn validate_selector(
+    selector: target_selector::ParsedSelector,
+    target_span: miette::SourceSpan,
+    workspace_root: &WorkspaceRoot,
+) -> Result<TargetSelector, TaskValidationError> {
+    use target_selector::ParsedSelector;
+
+    // The spans from the parser don't include the opening quote of the property.
+    let subspan = |span: std::ops::Range<usize>| target_span.subspan(span.start + 1, span.len());
+
+    Ok(match selector {
+        ParsedSelector::CurrentProject => TargetSelector::CurrentProject,
+        ParsedSelector::DependenciesOfCurrent => TargetSelector::DependenciesOfCurrent,
+        ParsedSelector::TransitiveDependenciesOfCurrent => {
+            TargetSelector::TransitiveDependenciesOfCurrent
+        }
+        ParsedSelector::DependentsOfCurrent => TargetSelector::DependentsOfCurrent,
+        ParsedSelector::Tagged(tag) => TargetSelector::Tagged(tag),
+        ParsedSelector::ProjectByName(name, span) => TargetSelector::SpecificDependency(
+            SpecificProjectSelector::ByName(name).with_span(subspan(span)),
+        ),
+        ParsedSelector::ProjectByPath(path, span) => {
+            let span = subspan(span);
+            TargetSelector::SpecificDependency(
+                SpecificProjectSelector::ByPath(
+                    workspace_root
+                        .subpath(path)
+                        .and_then(|p| p.validate())
+                        .map_err(|e| {
+                            TaskValidationError::InvalidPaths(vec![ConfigPathValidationError::new(
+                                e, span,
+                            )])
+                        })?,
+                )
+                .with_span(span),
+            )
+        }
+        ParsedSelector::Union(selectors) => TargetSelector::Union(
+            selectors
+                .into_iter()
+                .map(|(selector, span)| {
+                    Ok(validate_selector(selector, target_span, workspace_root)?
+                        .with_span(subspan(span)))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+#[derive(knuffel::Decode, Debug, Clone)]
 pub struct InputBlock {
     #[knuffel(children(name = "path"), unwrap(argument))]
-    paths: Vec<Glob>,
+    paths: Vec<Spanned<String>>,
 
     #[knuffel(children(name = "env_var"), unwrap(argument))]
-    env_vars: Vec<String>,
+    env_vars: Vec<Spanned<String>>,
 
     #[knuffel(children(name = "command"), unwrap(argument))]
-    commands: Vec<String>,
+    commands: Vec<Spanned<String>>,
+
+    #[knuffel(children(name = "when"))]
+    conditions: Vec<validated::Condition>,
 }
 
 mod target_selector {
@@ -148,17 +557,30 @@ mod target_selector {
     pub enum ParsedSelector {
         CurrentProject,
         DependenciesOfCurrent,
+        TransitiveDependenciesOfCurrent,
+        DependentsOfCurrent,
+        Tagged(String),
         ProjectByName(String, Range<usize>),
         ProjectByPath(Utf8PathBuf, Range<usize>),
+        Union(Vec<(ParsedSelector, Range<usize>)>),
     }
 
     pub fn parser() -> impl chumsky::Parser<char, ParsedSelector, Error = Simple<char>> {
         let is_package_char = |c: &char| c.is_alphabetic() || *c == '_' || *c == '-';
-        let is_path_char = |_: &char| true;
+        let is_tag_char = |c: &char| c.is_alphanumeric() || *c == '_' || *c == '-';
+        let is_path_char = |c: &char| *c != ',';
 
         let current_project = text::keyword("self").to(ParsedSelector::CurrentProject);
+        let dependents = just("self^").to(ParsedSelector::DependentsOfCurrent);
+        let transitive_dependencies =
+            just("^self...").to(ParsedSelector::TransitiveDependenciesOfCurrent);
         let dependencies = just("^self").to(ParsedSelector::DependenciesOfCurrent);
 
+        let tagged = just("tag:")
+            .ignore_then(filter(is_tag_char).repeated().at_least(1))
+            .collect::<String>()
+            .map(ParsedSelector::Tagged);
+
         let package_name = filter(is_package_char)
             .map(Some)
             .chain::<char, Vec<_>, _>(filter(is_package_char).repeated())
@@ -175,7 +597,25 @@ mod target_selector {
             })
             .map_with_span(ParsedSelector::ProjectByPath);
 
-        choice::<_, Simple<char>>((current_project, dependencies, package_path, package_name))
+        let selector = choice::<_, Simple<char>>((
+            dependents,
+            current_project,
+            transitive_dependencies,
+            dependencies,
+            tagged,
+            package_path,
+            package_name,
+        ));
+
+        selector
+            .map_with_span(|selector, span| (selector, span))
+            .separated_by(just(',').then_ignore(text::whitespace()))
+            .at_least(1)
+            .then_ignore(end())
+            .map(|mut selectors| match selectors.len() {
+                1 => selectors.remove(0).0,
+                _ => ParsedSelector::Union(selectors),
+            })
     }
 
     #[cfg(test)]
@@ -210,6 +650,46 @@ mod target_selector {
                     assert_eq!(name, "/lib/project");
                 }
             );
+
+            assert_matches!(
+                parser().parse("^self...").unwrap(),
+                ParsedSelector::TransitiveDependenciesOfCurrent
+            );
+
+            assert_matches!(
+                parser().parse("self^").unwrap(),
+                ParsedSelector::DependentsOfCurrent
+            );
+
+            assert_matches!(
+                parser().parse("tag:rust").unwrap(),
+                ParsedSelector::Tagged(tag) => {
+                    assert_eq!(tag, "rust");
+                }
+            );
+        }
+
+        #[test]
+        fn parsing_selector_unions() {
+            assert_matches!(
+                parser().parse("^self, tag:rust,/lib/project").unwrap(),
+                ParsedSelector::Union(selectors) => {
+                    assert_matches!(selectors.as_slice(), [
+                        (ParsedSelector::DependenciesOfCurrent, first),
+                        (ParsedSelector::Tagged(tag), second),
+                        (ParsedSelector::ProjectByPath(path, _), third),
+                    ] => {
+                        assert_eq!(*first, 0..5);
+                        assert_eq!(tag, "rust");
+                        assert_eq!(*second, 7..15);
+                        assert_eq!(path, "/lib/project");
+                        assert_eq!(*third, 16..28);
+                    });
+                }
+            );
+
+            assert!(parser().parse("self,").is_err());
+            assert!(parser().parse("self^^").is_err());
         }
     }
 }
@@ -1,8 +1,18 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
 use crate::{
     config::{
-        parsing, paths::ConfigPathValidationError, spanned::WithSpan, validated, ConfigSource,
-        UnvalidatedConfig, UnvalidatedProjectFile, UnvalidatedWorkspaceFile, ValidConfig,
-        ValidPath, ValidProjectFile, WorkspaceFile, WorkspaceRoot,
+        parsing::{
+            self, read_dependencies, validate_name, DuplicateProjectError, EmptyImportWarning,
+            ManifestDependencyWarning, ManifestResolver, NameKind, ProjectDefinedHere,
+            TaskDefinedHere, TaskNameError, TaskValidationError, TemplateDefinedHere,
+            TemplateError, UnknownExcludeWarning,
+        },
+        paths::ConfigPathValidationError,
+        spanned::WithSpan,
+        validated, ConfigSource, InterpolatedString, UnvalidatedConfig, UnvalidatedProjectFile,
+        UnvalidatedWorkspaceFile, ValidConfig, ValidPath, ValidProjectFile, WorkspaceFile,
+        WorkspaceRoot,
     },
     diagnostics::{CollectResults, ConfigError, DynDiagnostic},
 };
@@ -10,6 +20,10 @@ use crate::{
 pub struct Validator {
     workspace_root: WorkspaceRoot,
     errors: Vec<DynDiagnostic>,
+    warnings: Vec<DynDiagnostic>,
+    /// The definitions of any duplicate tasks we've reported, so that clashes between
+    /// workspace tasks are reported once rather than once per project.
+    reported_duplicates: HashSet<Vec<(String, usize)>>,
 }
 
 impl Validator {
@@ -17,9 +31,18 @@ impl Validator {
         Validator {
             workspace_root,
             errors: vec![],
+            warnings: vec![],
+            reported_duplicates: HashSet::new(),
         }
     }
 
+    /// Takes any warnings that have been encountered so far.
+    ///
+    /// Unlike errors these don't stop config from loading, but should be shown to the user.
+    pub fn take_warnings(&mut self) -> Vec<DynDiagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
     pub fn ok(&mut self) -> Result<(), ConfigError> {
         if self.errors.is_empty() {
             return Ok(());
@@ -41,6 +64,10 @@ impl Validator {
             .map(|project_file| self.validate_project_file(project_file))
             .collect::<Option<Vec<_>>>();
 
+        if let Some(project_files) = &project_files {
+            self.check_duplicate_projects(project_files);
+        }
+
         self.ok()?;
 
         let (workspace_file, project_files) = workspace_file
@@ -50,6 +77,7 @@ impl Validator {
         Ok(ValidConfig {
             workspace_file,
             project_files,
+            warnings: vec![],
         })
     }
 
@@ -57,16 +85,71 @@ impl Validator {
         &mut self,
         workspace: UnvalidatedWorkspaceFile,
     ) -> Option<WorkspaceFile> {
+        let workspace_root = ValidPath::from(workspace.workspace_root.clone());
+        let rules = workspace.config.check_rules();
+        let rules = self.record_errors(rules, &workspace.source);
+
+        let mut root_tasks = self.validate_tasks(
+            parsing::TaskBlock {
+                tasks: workspace.config.root_tasks,
+                ..parsing::TaskBlock::default()
+            },
+            &workspace_root,
+            &workspace_root,
+            &workspace.source,
+        )?;
+        self.resolve_task_overrides(&mut root_tasks);
+        rules?;
+
         Some(WorkspaceFile {
             workspace_root: workspace.workspace_root,
             config: validated::WorkspaceDefinition {
                 name: workspace.config.name,
                 project_paths: workspace.config.project_paths,
+                env_passthrough: workspace
+                    .config
+                    .env_passthrough
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                tasks: workspace.config.tasks,
+                root_tasks: root_tasks.tasks,
+                rules: workspace.config.rules,
             },
             source: workspace.source,
         })
     }
 
+    /// Reports any projects that share a name with another project in the workspace
+    fn check_duplicate_projects(&mut self, project_files: &[ValidProjectFile]) {
+        let mut projects_by_name = BTreeMap::<&str, Vec<&ValidProjectFile>>::new();
+        for project_file in project_files {
+            projects_by_name
+                .entry(project_file.config.project.as_str())
+                .or_default()
+                .push(project_file);
+        }
+
+        for (name, projects) in projects_by_name {
+            if projects.len() < 2 {
+                continue;
+            }
+            self.errors.push(DynDiagnostic::new(DuplicateProjectError {
+                name: name.to_owned(),
+                definitions: projects
+                    .into_iter()
+                    .map(|project_file| {
+                        DynDiagnostic::new(ProjectDefinedHere {
+                            name: name.to_owned(),
+                            span: project_file.config.project.span,
+                        })
+                        .with_source_code(project_file.source.clone())
+                    })
+                    .collect(),
+            }));
+        }
+    }
+
     fn validate_project_file(&mut self, file: UnvalidatedProjectFile) -> Option<ValidProjectFile> {
         let config =
             self.validate_project_definition(file.config, &file.project_root, &file.source)?;
@@ -84,76 +167,603 @@ impl Validator {
         project_path: &ValidPath,
         config_source: &ConfigSource,
     ) -> Option<validated::ProjectDefinition> {
-        let dependencies = project
-            .dependencies
-            .projects
-            .into_iter()
-            .map(|p| {
-                let span = p.span;
-                Ok::<_, ConfigPathValidationError>(
-                    p.into_inner()
+        let dependencies_span = project.dependencies.as_ref().map(|block| block.span.0);
+        let (declared_dependencies, dependency_manifests) = match project.dependencies {
+            Some(block) => (
+                block.projects,
+                vec![
+                    (validated::Manifest::Cargo, block.from_cargo),
+                    (validated::Manifest::PackageJson, block.from_package_json),
+                    (validated::Manifest::GoMod, block.from_go_mod),
+                ],
+            ),
+            None => (vec![], vec![]),
+        };
+
+        let dependencies = declared_dependencies
+            .into_iter()
+            .map(|dependency| {
+                let span = dependency.path.span;
+                Ok::<_, ConfigPathValidationError>(validated::ProjectDependency {
+                    path: dependency
+                        .path
+                        .into_inner()
                         .validate_relative_to(project_path)?
                         .with_span(span),
-                )
+                    kind: dependency.kind,
+                    span: dependency.span.0,
+                    inferred_from: None,
+                })
             })
             .collect_results();
 
-        let tasks = self.validate_tasks(project.tasks, project_path, config_source);
+        let dependency_manifests = dependency_manifests
+            .into_iter()
+            .flat_map(|(manifest, imports)| {
+                imports
+                    .into_iter()
+                    .map(move |import| manifest.with_span(import.span.0))
+            })
+            .collect();
+
+        let env_files = project
+            .env_files
+            .into_iter()
+            .map(|path| path.validate_relative_to(project_path))
+            .collect_results();
 
-        let (dependencies, tasks) = self.record_errors(dependencies, config_source).zip(tasks)?;
+        let name = validate_name(NameKind::Project, &project.project).map_err(|e| vec![e]);
+        let name = self.record_errors(name, config_source);
 
+        let tasks = self.validate_tasks(project.tasks, project_path, project_path, config_source);
+
+        let dependencies = self.record_errors(dependencies, config_source);
+        let env_files = self.record_errors(env_files, config_source);
+
+        name?;
         Some(validated::ProjectDefinition {
             project: project.project,
-            dependencies,
-            tasks,
+            tags: project.tags,
+            dependencies: dependencies?,
+            dependencies_span,
+            dependency_manifests,
+            env_files: env_files?,
+            tasks: tasks?,
         })
     }
 
+    /// Adds any dependencies on other projects listed in a projects language specific manifests.
+    ///
+    /// Manifest dependencies that don't point at a project in the workspace are
+    /// reported as warnings, as nabs has no way to track them.
+    pub fn infer_dependencies(&mut self, project_files: &mut [ValidProjectFile]) {
+        let mut resolver = ManifestResolver::new(
+            self.workspace_root.clone(),
+            project_files
+                .iter()
+                .map(|project_file| project_file.project_root.clone()),
+        );
+
+        for project_file in project_files.iter_mut() {
+            let project = &mut project_file.config;
+            for manifest in &project.dependency_manifests {
+                let dependencies = match read_dependencies(
+                    **manifest,
+                    &project_file.project_root,
+                    &self.workspace_root,
+                    manifest.span,
+                ) {
+                    Ok(dependencies) => dependencies,
+                    Err(error) => {
+                        self.record_error(error, &project_file.source);
+                        continue;
+                    }
+                };
+
+                for dependency in dependencies {
+                    let Some(path) = resolver.resolve(dependency.target) else {
+                        self.warnings.push(
+                            DynDiagnostic::new(ManifestDependencyWarning {
+                                manifest: manifest.file_name(),
+                                dependency: dependency.declared,
+                                span: manifest.span,
+                            })
+                            .with_source_code(project_file.source.clone()),
+                        );
+                        continue;
+                    };
+
+                    // Anything listed explicitly takes precedence over the manifest
+                    if path == project_file.project_root
+                        || project
+                            .dependencies
+                            .iter()
+                            .any(|existing| *existing.path == path)
+                    {
+                        continue;
+                    }
+                    project.dependencies.push(validated::ProjectDependency {
+                        path: path.with_span(manifest.span),
+                        kind: dependency.kind,
+                        span: manifest.span,
+                        inferred_from: Some(**manifest),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Validates a block of tasks.
+    ///
+    /// Imports are resolved relative to `relative_to` (i.e. the file the tasks came
+    /// from), whereas paths inside the tasks themselves are relative to `project_root`.
     pub fn validate_tasks(
         &mut self,
         tasks: parsing::TaskBlock,
         relative_to: &ValidPath,
+        project_root: &ValidPath,
         source_code: &ConfigSource,
     ) -> Option<validated::TaskBlock> {
-        let imports = tasks
-            .imports
+        let mut imports = Vec::new();
+        let mut import_errors = Vec::new();
+        for import in tasks.imports {
+            let span = import.path.span();
+            let pattern = import.path.as_str().to_owned();
+            let namespace = import.namespace;
+            match import.path.expand_relative_to(relative_to) {
+                Ok(paths) if paths.is_empty() => {
+                    self.warnings.push(
+                        DynDiagnostic::new(EmptyImportWarning { pattern, span })
+                            .with_source_code(source_code.clone()),
+                    );
+                }
+                Ok(paths) => imports.extend(paths.into_iter().map(|path| validated::TaskImport {
+                    path: path.with_span(span),
+                    namespace: namespace.clone(),
+                })),
+                Err(error) => import_errors.push(error),
+            }
+        }
+        let excludes = tasks.excludes;
+        let imports = self.record_errors(
+            if import_errors.is_empty() {
+                Ok(imports)
+            } else {
+                Err(import_errors)
+            },
+            source_code,
+        );
+
+        let templates = tasks
+            .templates
             .into_iter()
-            .map(|path| path.validate_relative_to(relative_to))
-            .collect_results();
+            .map(|template| self.validate_template(template, project_root, source_code))
+            .collect::<Option<Vec<_>>>();
 
-        let imports = self.record_errors(imports, source_code);
+        let uses = tasks
+            .uses
+            .into_iter()
+            .map(|template_use| self.validate_template_use(template_use, source_code))
+            .collect::<Option<Vec<_>>>();
 
         let tasks = tasks
             .tasks
             .into_iter()
-            .map(|task| self.validate_task(task, source_code))
+            .map(|task| {
+                let task = self.validate_task(task, project_root, source_code)?;
+                let variables = check_template_variables(task.interpolated_strings(), None);
+                self.record_errors(variables, source_code)?;
+                Some(task)
+            })
             .collect::<Option<Vec<_>>>();
 
-        let (imports, tasks) = imports.zip(tasks)?;
+        Some(validated::TaskBlock {
+            excludes,
+            imports: imports?,
+            templates: templates?,
+            uses: uses?,
+            tasks: tasks?,
+        })
+    }
+
+    fn validate_template(
+        &mut self,
+        template: parsing::TemplateDefinition,
+        project_root: &ValidPath,
+        config_source: &ConfigSource,
+    ) -> Option<validated::TaskTemplate> {
+        let variables = template
+            .variables
+            .into_iter()
+            .map(|variable| variable.into_inner())
+            .collect::<Vec<_>>();
+
+        let targets_tags = template
+            .targets_tags
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if !targets_tags.is_empty() {
+            if !variables.is_empty() {
+                self.record_error(
+                    TemplateError::TargetedTemplateWithVariables {
+                        name: template.name.to_string(),
+                        span: template.name.span,
+                    },
+                    config_source,
+                );
+                return None;
+            }
+
+            // The paths in these tasks are relative to whichever project has the
+            // tag, so they're validated when the template is applied to a project.
+            return Some(validated::TaskTemplate {
+                name: template.name,
+                variables,
+                targets_tags,
+                tasks: Vec::new(),
+                targeted_tasks: template.tasks,
+                source: config_source.clone(),
+            });
+        }
+
+        let tasks = template
+            .tasks
+            .into_iter()
+            .map(|task| self.validate_task(task, project_root, config_source))
+            .collect::<Option<Vec<_>>>()?;
+
+        let undeclared = check_template_variables(
+            tasks.iter().flat_map(|task| task.interpolated_strings()),
+            Some(&variables),
+        );
+        self.record_errors(undeclared, config_source)?;
+
+        Some(validated::TaskTemplate {
+            name: template.name,
+            variables,
+            targets_tags,
+            tasks,
+            targeted_tasks: Vec::new(),
+            source: config_source.clone(),
+        })
+    }
+
+    /// Validates the tasks of a template that targets tags, relative to the root
+    /// of one of the projects it's being applied to.
+    pub fn validate_targeted_template(
+        &mut self,
+        template: &validated::TaskTemplate,
+        project_root: &ValidPath,
+    ) -> Option<Vec<validated::TaskDefinition>> {
+        let tasks = template
+            .targeted_tasks
+            .iter()
+            .map(|task| self.validate_task(task.clone(), project_root, &template.source))
+            .collect::<Option<Vec<_>>>()?;
+
+        let undeclared = check_template_variables(
+            tasks.iter().flat_map(|task| task.interpolated_strings()),
+            Some(&[]),
+        );
+        self.record_errors(undeclared, &template.source)?;
+
+        Some(tasks)
+    }
+
+    fn validate_template_use(
+        &mut self,
+        template_use: parsing::TemplateUse,
+        config_source: &ConfigSource,
+    ) -> Option<validated::TemplateUse> {
+        let variables = template_use
+            .variables
+            .into_iter()
+            .map(|(name, value)| InterpolatedString::parse(value).map(|value| (name, value)))
+            .collect_results()
+            .map_err(|errors| errors.into_iter().flatten().collect::<Vec<_>>());
+        let variables = self.record_errors(variables, config_source)?;
+
+        let nested = check_template_variables(variables.iter().map(|(_, value)| value), None);
+        self.record_errors(nested, config_source)?;
+        let matrix_variables =
+            check_matrix_variables(variables.iter().map(|(_, value)| value), &[]);
+        self.record_errors(matrix_variables, config_source)?;
+
+        Some(validated::TemplateUse {
+            template: template_use.template,
+            variables: variables.into_iter().collect(),
+            source: config_source.clone(),
+        })
+    }
+
+    /// Expands any templates used in a block of tasks into the tasks they define.
+    ///
+    /// This should be done after imports have been resolved, as templates are
+    /// usually defined in a task file that the project imports.
+    pub fn apply_templates(&mut self, tasks: &mut validated::TaskBlock) {
+        let mut templates = HashMap::<&str, &validated::TaskTemplate>::new();
+        for template in &tasks.templates {
+            match templates.get(template.name.as_str()) {
+                None => {
+                    templates.insert(template.name.as_str(), template);
+                }
+                // The same file has been imported into more than one namespace,
+                // which gives us more than one copy of the same definition.
+                Some(existing) if existing.is_same_definition(template) => {}
+                Some(existing) => {
+                    let definitions = [existing, template]
+                        .into_iter()
+                        .map(|template| {
+                            DynDiagnostic::new(TemplateDefinedHere {
+                                name: template.name.to_string(),
+                                span: template.name.span,
+                            })
+                            .with_source_code(template.source.clone())
+                        })
+                        .collect();
+                    self.errors
+                        .push(DynDiagnostic::new(TemplateError::DuplicateTemplate {
+                            name: template.name.to_string(),
+                            definitions,
+                        }));
+                }
+            }
+        }
 
-        Some(validated::TaskBlock { imports, tasks })
+        let mut new_tasks = Vec::new();
+        for template_use in &tasks.uses {
+            let template = match templates.get(template_use.template.as_str()) {
+                Some(template) => template,
+                None => {
+                    let error = TemplateError::UnknownTemplate {
+                        name: template_use.template.to_string(),
+                        span: template_use.template.span,
+                    };
+                    self.record_error(error, &template_use.source);
+                    continue;
+                }
+            };
+
+            let missing = template
+                .variables
+                .iter()
+                .filter(|variable| !template_use.variables.contains_key(*variable))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let mut errors = template_use
+                .variables
+                .iter()
+                .filter(|(name, _)| !template.variables.contains(name))
+                .map(|(name, value)| TemplateError::UnknownVariable {
+                    template: template.name.to_string(),
+                    name: name.clone(),
+                    span: value.span,
+                })
+                .collect::<Vec<_>>();
+
+            if !missing.is_empty() {
+                errors.push(TemplateError::MissingVariables {
+                    name: template.name.to_string(),
+                    missing,
+                    span: template_use.template.span,
+                });
+            }
+
+            if !errors.is_empty() {
+                self.record_errors(Err::<(), _>(errors), &template_use.source);
+                continue;
+            }
+
+            new_tasks.extend(
+                template
+                    .tasks
+                    .iter()
+                    .map(|task| task.substitute(&template_use.variables)),
+            );
+        }
+
+        tasks.tasks.extend(new_tasks);
+    }
+
+    /// Resolves tasks that have the same name, keeping whichever has `override=true`
+    /// and reporting any other duplicates as errors.
+    ///
+    /// This should be done once imports & templates have been resolved.
+    pub fn resolve_task_overrides(&mut self, tasks: &mut validated::TaskBlock) {
+        let mut indices_by_name = BTreeMap::<&str, Vec<usize>>::new();
+        for (index, task) in tasks.tasks.iter().enumerate() {
+            indices_by_name
+                .entry(task.name.as_str())
+                .or_default()
+                .push(index);
+        }
+
+        let mut overridden = HashSet::new();
+        for (name, indices) in indices_by_name {
+            if indices.len() < 2 {
+                continue;
+            }
+
+            let overrides = indices
+                .iter()
+                .copied()
+                .filter(|index| tasks.tasks[*index].overrides)
+                .collect::<Vec<_>>();
+
+            if let [winner] = overrides.as_slice() {
+                overridden.extend(indices.into_iter().filter(|index| index != winner));
+                continue;
+            }
+
+            let reported = indices
+                .iter()
+                .map(|index| {
+                    let task = &tasks.tasks[*index];
+                    (task.source.filename().to_owned(), task.name.span.offset())
+                })
+                .collect();
+            if !self.reported_duplicates.insert(reported) {
+                continue;
+            }
+
+            let definitions = |indices: Vec<usize>| {
+                indices
+                    .into_iter()
+                    .map(|index| {
+                        let task = &tasks.tasks[index];
+                        DynDiagnostic::new(TaskDefinedHere {
+                            name: name.to_owned(),
+                            span: task.name.span,
+                        })
+                        .with_source_code(task.source.clone())
+                    })
+                    .collect()
+            };
+
+            self.errors
+                .push(DynDiagnostic::new(if overrides.is_empty() {
+                    TaskNameError::Duplicate {
+                        name: name.to_owned(),
+                        definitions: definitions(indices),
+                    }
+                } else {
+                    TaskNameError::MultipleOverrides {
+                        name: name.to_owned(),
+                        definitions: definitions(overrides),
+                    }
+                }));
+        }
+
+        let mut index = 0;
+        tasks.tasks.retain(|_| {
+            let keep = !overridden.contains(&index);
+            index += 1;
+            keep
+        });
+    }
+
+    /// Warns about any `exclude` in a project that doesn't name one of the tasks
+    /// the workspace file would have added to it, as it has no effect.
+    pub fn check_excludes(
+        &mut self,
+        project_file: &ValidProjectFile,
+        workspace_task_names: &HashSet<String>,
+    ) {
+        for exclude in &project_file.config.tasks.excludes {
+            if !workspace_task_names.contains(exclude.as_str()) {
+                self.warnings.push(
+                    DynDiagnostic::new(UnknownExcludeWarning {
+                        name: exclude.to_string(),
+                        span: exclude.span,
+                    })
+                    .with_source_code(project_file.source.clone()),
+                );
+            }
+        }
     }
 
     fn validate_task(
         &mut self,
         task: parsing::TaskDefinition,
+        project_root: &ValidPath,
         config_source: &ConfigSource,
     ) -> Option<validated::TaskDefinition> {
+        let name = validate_name(NameKind::Task, &task.name).map_err(|e| vec![e]);
+        let name = self.record_errors(name, config_source);
+
+        let conditions = task.check_conditions();
+        let conditions = self.record_errors(conditions, config_source);
+
+        let matrix = task.check_matrix();
+        let matrix = self.record_errors(matrix, config_source);
+
         let requires = task
             .requires
             .into_iter()
             .map(|r| r.parse(&self.workspace_root))
             .collect_results();
 
-        let requires = self.record_errors(requires, config_source)?;
+        let requires = self.record_errors(requires, config_source);
+
+        let after = task
+            .after
+            .into_iter()
+            .map(|r| r.parse(&self.workspace_root))
+            .collect_results();
+        let after = self.record_errors(after, config_source);
+
+        let commands = task
+            .commands
+            .into_iter()
+            .map(|command| command.parse())
+            .collect_results()
+            .map_err(|errors| errors.into_iter().flatten().collect::<Vec<_>>());
+        let commands = self.record_errors(commands, config_source);
+
+        let input_blocks = task
+            .input_blocks
+            .into_iter()
+            .map(|block| block.parse())
+            .collect_results()
+            .map_err(|errors| errors.into_iter().flatten().collect::<Vec<_>>());
+        let input_blocks = self.record_errors(input_blocks, config_source);
+
+        let cwd = task
+            .cwd
+            .map(|cwd| cwd.validate_relative_to(project_root))
+            .transpose()
+            .map_err(|e| vec![e]);
+        let cwd = self.record_errors(cwd, config_source);
+
+        let env = task
+            .env
+            .into_iter()
+            .map(|var| InterpolatedString::parse(var.value).map(|value| (var.name, value)))
+            .collect_results()
+            .map_err(|errors| errors.into_iter().flatten().collect::<Vec<_>>());
+        let env = self.record_errors(env, config_source);
+
+        let env_files = task
+            .env_files
+            .into_iter()
+            .map(|path| path.validate_relative_to(project_root))
+            .collect_results();
+        let env_files = self.record_errors(env_files, config_source);
 
-        Some(validated::TaskDefinition {
+        name?;
+        conditions?;
+        matrix?;
+        let task = validated::TaskDefinition {
             name: task.name,
-            commands: task.commands,
-            requires,
-            input_blocks: task.input_blocks.into_iter().map(Into::into).collect(),
+            overrides: task.overrides,
+            description: task.description,
+            cwd: cwd?,
+            env: env?,
+            env_files: env_files?,
+            commands: commands?,
+            requires: requires?,
+            after: after?,
+            input_blocks: input_blocks?,
+            conditions: task.conditions,
+            matrix: task.matrix,
             source: config_source.clone(),
-        })
+        };
+
+        let matrix_variables = check_matrix_variables(task.interpolated_strings(), &task.matrix);
+        self.record_errors(matrix_variables, config_source)?;
+
+        Some(task)
+    }
+
+    fn record_error<E>(&mut self, error: E, config_source: &ConfigSource)
+    where
+        E: miette::Diagnostic + Send + Sync + 'static,
+    {
+        self.errors
+            .push(DynDiagnostic::new(error).with_source_code(config_source.clone()));
     }
 
     fn record_errors<T, E>(
@@ -177,6 +787,64 @@ impl Validator {
     }
 }
 
+/// Checks that `strings` only reference matrix variables from `declared`
+fn check_matrix_variables<'a>(
+    strings: impl Iterator<Item = &'a InterpolatedString>,
+    declared: &[validated::MatrixVariable],
+) -> Result<(), Vec<TaskValidationError>> {
+    let errors = strings
+        .flat_map(|string| {
+            string
+                .matrix_variables()
+                .filter(|name| {
+                    !declared
+                        .iter()
+                        .any(|variable| variable.name.as_str() == *name)
+                })
+                .map(|name| TaskValidationError::UndeclaredMatrixVariable {
+                    name: name.to_owned(),
+                    span: string.span,
+                })
+        })
+        .collect::<Vec<_>>();
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(())
+}
+
+/// Checks that template variables are only used inside templates that declare them.
+fn check_template_variables<'a>(
+    strings: impl Iterator<Item = &'a InterpolatedString>,
+    declared: Option<&[String]>,
+) -> Result<(), Vec<TemplateError>> {
+    let mut errors = Vec::new();
+    for string in strings {
+        for name in string.template_variables() {
+            match declared {
+                None => errors.push(TemplateError::VariableOutsideTemplate {
+                    name: name.to_owned(),
+                    span: string.span,
+                }),
+                Some(declared) if !declared.iter().any(|variable| variable == name) => {
+                    errors.push(TemplateError::UndeclaredVariable {
+                        name: name.to_owned(),
+                        span: string.span,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use camino::Utf8PathBuf;
@@ -199,7 +867,9 @@ mod tests {
                 }
 
                 tasks {
-                    task "build" {
+                    task "build" cwd="../library" {
+                        env "CARGO_TARGET_DIR" "{workspace.root}/target"
+
                         // These technically aren't valid, but the ways they're wrong
                         // are validated later on in the process.
                         requires "a-task-in-library" in="library"
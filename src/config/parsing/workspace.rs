@@ -1,4 +1,7 @@
-use super::super::Glob;
+use super::{
+    super::{validated::DependencyRule, Glob},
+    tasks::{TaskBlock, TaskDefinition, TaskImport, TemplateDefinition},
+};
 
 #[derive(knuffel::Decode, Debug)]
 pub struct WorkspaceDefinition {
@@ -7,8 +10,92 @@ pub struct WorkspaceDefinition {
 
     #[knuffel(children(name = "project_path"), unwrap(argument))]
     pub project_paths: Vec<Glob>,
+
+    #[knuffel(children(name = "env_passthrough"), unwrap(arguments))]
+    pub env_passthrough: Vec<Vec<Glob>>,
+
+    #[knuffel(children(name = "tasks"))]
+    pub tasks: Vec<WorkspaceTasks>,
+
+    /// Tasks that belong to the workspace itself, rather than any project.
+    #[knuffel(children(name = "task"))]
+    pub root_tasks: Vec<TaskDefinition>,
+
+    #[knuffel(children(name = "rule"))]
+    pub rules: Vec<DependencyRule>,
+}
+
+/// A block of tasks that should be added to every project in the workspace,
+/// or just those matching `projects` and/or `tag` if they're provided.
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct WorkspaceTasks {
+    #[knuffel(property)]
+    pub projects: Option<Glob>,
+
+    #[knuffel(property)]
+    pub tag: Option<String>,
+
+    #[knuffel(children(name = "import"))]
+    imports: Vec<TaskImport>,
+
+    #[knuffel(children(name = "template"))]
+    templates: Vec<TemplateDefinition>,
+
+    #[knuffel(children(name = "task"))]
+    tasks: Vec<TaskDefinition>,
+}
+
+impl WorkspaceTasks {
+    pub fn task_block(&self) -> TaskBlock {
+        TaskBlock {
+            imports: self.imports.clone(),
+            templates: self.templates.clone(),
+            tasks: self.tasks.clone(),
+            ..TaskBlock::default()
+        }
+    }
+}
+
+impl WorkspaceDefinition {
+    /// Checks that every matcher in the dependency rules actually narrows down
+    /// the projects it matches.
+    pub(super) fn check_rules(&self) -> Result<(), Vec<WorkspaceValidationError>> {
+        let mut errors = Vec::new();
+        for rule in &self.rules {
+            let matchers = rule
+                .from
+                .iter()
+                .map(|matcher| ("from", matcher))
+                .chain(rule.allow.iter().map(|matcher| ("allow", matcher)))
+                .chain(rule.deny.iter().map(|matcher| ("deny", matcher)));
+
+            for (kind, matcher) in matchers {
+                if matcher.projects.is_none() && matcher.tag.is_none() {
+                    errors.push(WorkspaceValidationError::EmptyMatcher {
+                        kind,
+                        rule: rule.name.to_string(),
+                        span: matcher.span.0,
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 #[derive(thiserror::Error, miette::Diagnostic, Debug)]
-#[error("Workspace failed validation")]
-pub enum WorkspaceValidationError {}
+pub enum WorkspaceValidationError {
+    #[error("`{kind}` in the rule {rule} needs a projects or tag property")]
+    #[diagnostic(help("for example `{kind} projects=\"libs/*\"` or `{kind} tag=\"library\"`"))]
+    EmptyMatcher {
+        kind: &'static str,
+        rule: String,
+        #[label("this would match every project")]
+        span: miette::SourceSpan,
+    },
+}
@@ -10,9 +10,13 @@ pub struct ConfigPath {
 }
 
 impl ConfigPath {
-    // pub fn span(&self) -> miette::SourceSpan {
-    //     self.span.clone()
-    // }
+    pub fn as_str(&self) -> &str {
+        self.inner.as_str()
+    }
+
+    pub fn span(&self) -> miette::SourceSpan {
+        self.span
+    }
 
     pub fn into_inner(self) -> Utf8PathBuf {
         self.inner
@@ -26,6 +30,92 @@ impl ConfigPath {
             .join_and_validate(self.inner)
             .map_err(|e| ConfigPathValidationError::new(e, self.span))
     }
+
+    /// Like `validate_relative_to`, but treats the path as a glob if it contains any
+    /// glob characters, and expands directories into the task files they contain.
+    ///
+    /// Matches are returned in a deterministic (sorted) order.
+    pub fn expand_relative_to(
+        self,
+        relative_to: &ValidPath,
+    ) -> Result<Vec<ValidPath>, ConfigPathValidationError> {
+        let span = self.span;
+
+        let pattern = if is_glob(self.inner.as_str()) {
+            relative_to
+                .join(self.inner)
+                .map_err(|e| ConfigPathValidationError::new(e, span))?
+        } else {
+            let path = self.validate_relative_to(relative_to)?;
+            if !path.full_path().is_dir() {
+                return Ok(vec![path]);
+            }
+            path.join(TASK_FILES_GLOB)
+                .map_err(|e| ConfigPathValidationError::new(e, span))?
+        };
+
+        let glob = globset::GlobBuilder::new(pattern.subpath().as_str())
+            .literal_separator(true)
+            .build()
+            .map_err(|e| ConfigPathValidationError::InvalidGlob(e, span))?
+            .compile_matcher();
+
+        // There's no point walking any further up the tree than the first
+        // component that contains a glob.
+        let base = pattern
+            .subpath()
+            .components()
+            .take_while(|component| !is_glob(component.as_str()))
+            .collect::<Utf8PathBuf>();
+
+        // Nor any deeper than the pattern goes, unless it can match any depth.
+        let max_depth = (!pattern.subpath().as_str().contains("**"))
+            .then(|| pattern.subpath().components().count() - base.components().count());
+
+        let base = pattern.workspace_root.0.join(base);
+        if !base.exists() {
+            return Ok(vec![]);
+        }
+
+        // Hidden directories (.git and the like) are skipped, unless they're part of the base.
+        let mut paths = ignore::WalkBuilder::new(base)
+            .standard_filters(false)
+            .max_depth(max_depth)
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || !entry
+                        .file_type()
+                        .is_some_and(|file_type| file_type.is_dir())
+                    || !entry.file_name().to_string_lossy().starts_with('.')
+            })
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| {
+                let path = Utf8PathBuf::from_path_buf(entry.into_path()).ok()?;
+                let subpath = path.strip_prefix(&pattern.workspace_root.0).ok()?;
+                glob.is_match(subpath).then_some(path)
+            })
+            .map(|path| {
+                pattern
+                    .workspace_root
+                    .normalise_absolute(path)
+                    .map_err(|e| ConfigPathValidationError::new(e, span))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        paths.sort();
+        paths.dedup();
+
+        Ok(paths)
+    }
+}
+
+/// The files a directory import picks up, so other files can sit alongside them.
+const TASK_FILES_GLOB: &str = "*.{nabs,kdl}";
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[', '{'])
 }
 
 #[derive(thiserror::Error, Debug, miette::Diagnostic)]
@@ -55,6 +145,11 @@ pub enum ConfigPathValidationError {
         Utf8PathBuf,
         #[label("the path is referenced here")] miette::SourceSpan,
     ),
+    #[error("Invalid glob: {0}")]
+    InvalidGlob(
+        globset::Error,
+        #[label("the glob is referenced here")] miette::SourceSpan,
+    ),
 }
 
 impl ConfigPathValidationError {
@@ -636,4 +731,50 @@ mod tests {
             Utf8PathBuf::from("/hello.json"),
         );
     }
+
+    #[rstest]
+    #[case("/tasks/*.nabs", &["tasks/a.nabs", "tasks/b.nabs"])]
+    #[case("../tasks/*.nabs", &["tasks/a.nabs", "tasks/b.nabs"])]
+    #[case(
+        "/tasks/**/*.nabs",
+        &["tasks/a.nabs", "tasks/b.nabs", "tasks/nested/c.nabs"]
+    )]
+    #[case("/tasks", &["tasks/a.nabs", "tasks/b.nabs", "tasks/d.kdl"])]
+    #[case("/tasks/a.nabs", &["tasks/a.nabs"])]
+    #[case("/tasks/*.txt", &[])]
+    #[case("/missing/*.nabs", &[])]
+    #[case("/*/*.nabs", &["tasks/a.nabs", "tasks/b.nabs"])]
+    #[case("/.tasks/*.nabs", &[".tasks/e.nabs"])]
+    fn test_expanding_globs(#[case] import: &str, #[case] expected_subpaths: &[&str]) {
+        let test_files = TestFiles::new()
+            .with_file("tasks/b.nabs", "")
+            .with_file("tasks/a.nabs", "")
+            .with_file("tasks/README.md", "")
+            .with_file("tasks/d.kdl", "")
+            .with_file("tasks/nested/c.nabs", "")
+            .with_file("tasks/.cache/c.nabs", "")
+            .with_file(".tasks/e.nabs", "")
+            .with_file("project/project.kdl", "");
+
+        let project_path = test_files
+            .root()
+            .subpath("project")
+            .unwrap()
+            .validate()
+            .unwrap();
+        let import = ConfigPath {
+            span: (0, 0).into(),
+            inner: import.into(),
+        };
+
+        let paths = import.expand_relative_to(&project_path).unwrap();
+
+        assert_eq!(
+            paths
+                .iter()
+                .map(|p| p.as_subpath().as_str())
+                .collect::<Vec<_>>(),
+            expected_subpaths
+        );
+    }
 }
@@ -69,6 +69,21 @@ where
     }
 }
 
+/// The span of a whole node, for nodes that have nothing else to hang a span off.
+///
+/// Use with `#[knuffel(span)]`.
+#[derive(Clone, Copy, Debug)]
+pub struct NodeSpan(pub miette::SourceSpan);
+
+impl<S> knuffel::traits::DecodeSpan<S> for NodeSpan
+where
+    S: ErrorSpan,
+{
+    fn decode_span(span: &S, _ctx: &mut knuffel::decode::Context<S>) -> Self {
+        NodeSpan(span.clone().into())
+    }
+}
+
 pub trait WithSpan: Sized {
     fn with_span(self, span: miette::SourceSpan) -> Spanned<Self>;
 }
@@ -4,7 +4,63 @@ use super::{super::paths::ValidPath, tasks};
 
 #[derive(Debug)]
 pub struct ProjectDefinition {
-    pub project: String,
-    pub dependencies: Vec<Spanned<ValidPath>>,
+    pub project: Spanned<String>,
+    pub tags: Vec<String>,
+    pub dependencies: Vec<ProjectDependency>,
+    /// Where the dependencies block is in the project file, if there is one
+    pub dependencies_span: Option<miette::SourceSpan>,
+    /// Manifests that further dependencies should be read from
+    pub dependency_manifests: Vec<Spanned<Manifest>>,
+    pub env_files: Vec<ValidPath>,
     pub tasks: tasks::TaskBlock,
 }
+
+#[derive(Debug)]
+pub struct ProjectDependency {
+    pub path: Spanned<ValidPath>,
+    pub kind: DependencyKind,
+    /// The span of the whole `project` node, or of the manifest directive it was read from
+    pub span: miette::SourceSpan,
+    /// The manifest this dependency was read from, if it wasn't listed explicitly
+    pub inferred_from: Option<Manifest>,
+}
+
+/// What a project needs one of its dependencies for
+#[derive(knuffel::DecodeScalar, Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum DependencyKind {
+    /// Needed when the project runs.  This is the default.
+    #[default]
+    Runtime,
+    /// Only needed to build the project.
+    Build,
+    /// Only needed when working on the project, e.g. test fixtures.
+    Dev,
+}
+
+impl std::fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyKind::Runtime => write!(f, "runtime"),
+            DependencyKind::Build => write!(f, "build"),
+            DependencyKind::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+/// A language specific manifest that can list dependencies on other projects
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Manifest {
+    Cargo,
+    PackageJson,
+    GoMod,
+}
+
+impl Manifest {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Manifest::Cargo => "Cargo.toml",
+            Manifest::PackageJson => "package.json",
+            Manifest::GoMod => "go.mod",
+        }
+    }
+}
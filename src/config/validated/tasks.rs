@@ -1,45 +1,318 @@
-use crate::config::{ConfigSource, Glob};
+use std::collections::{BTreeMap, HashSet};
 
-use super::super::{paths::ValidPath, spanned::Spanned};
+use crate::config::{ConfigSource, InterpolatedString};
+
+use super::super::{
+    parsing,
+    paths::ValidPath,
+    spanned::{NodeSpan, Spanned, WithSpan},
+};
 
 #[derive(Debug, Default)]
 pub struct TaskBlock {
-    pub(in crate::config) imports: Vec<ValidPath>,
+    /// Names of workspace tasks that shouldn't be added to this project
+    pub(in crate::config) excludes: Vec<Spanned<String>>,
+
+    pub(in crate::config) imports: Vec<TaskImport>,
+
+    pub(in crate::config) templates: Vec<TaskTemplate>,
+
+    pub(in crate::config) uses: Vec<TemplateUse>,
 
     pub tasks: Vec<TaskDefinition>,
 }
 
 #[derive(Debug)]
+pub struct TaskImport {
+    pub path: Spanned<ValidPath>,
+
+    pub namespace: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskTemplate {
+    pub name: Spanned<String>,
+
+    pub variables: Vec<String>,
+
+    /// Projects with any of these tags have this template applied automatically
+    pub targets_tags: Vec<String>,
+
+    pub tasks: Vec<TaskDefinition>,
+
+    /// The unvalidated tasks of a template that targets tags, which are validated
+    /// separately for each project the template is applied to.
+    pub(in crate::config) targeted_tasks: Vec<parsing::TaskDefinition>,
+
+    pub source: ConfigSource,
+}
+
+#[derive(Debug)]
+pub struct TemplateUse {
+    pub template: Spanned<String>,
+
+    pub variables: BTreeMap<String, InterpolatedString>,
+
+    pub source: ConfigSource,
+}
+
+#[derive(Clone, Debug)]
 pub struct TaskDefinition {
-    pub name: String,
+    pub name: Spanned<String>,
 
-    pub commands: Vec<String>,
+    pub overrides: bool,
+
+    /// A summary of what the task does, for `nabs tasks`
+    pub description: Option<String>,
+
+    pub cwd: Option<ValidPath>,
+
+    pub env: Vec<(String, InterpolatedString)>,
+
+    pub env_files: Vec<ValidPath>,
+
+    pub commands: Vec<TaskCommand>,
 
     pub requires: Vec<TaskRequires>,
 
+    pub after: Vec<TaskRequires>,
+
     pub input_blocks: Vec<InputBlock>,
 
+    /// The task is disabled unless all of these hold.
+    pub conditions: Vec<Condition>,
+
+    /// The task is run once for every combination of these variables values
+    pub matrix: Vec<MatrixVariable>,
+
     pub source: ConfigSource,
 }
 
-#[derive(Debug)]
+impl TaskDefinition {
+    pub(in crate::config) fn interpolated_strings(
+        &self,
+    ) -> impl Iterator<Item = &InterpolatedString> {
+        self.env
+            .iter()
+            .map(|(_, value)| value)
+            .chain(self.commands.iter().map(|command| &command.command))
+            .chain(self.input_blocks.iter().flat_map(|block| {
+                block
+                    .paths
+                    .iter()
+                    .chain(&block.env_vars)
+                    .chain(&block.commands)
+            }))
+    }
+
+    /// Creates a copy of this task with any template variables replaced by `values`
+    pub(in crate::config) fn substitute(
+        &self,
+        values: &BTreeMap<String, InterpolatedString>,
+    ) -> TaskDefinition {
+        let substitute_all = |strings: &[InterpolatedString]| {
+            strings
+                .iter()
+                .map(|s| s.substitute(values))
+                .collect::<Vec<_>>()
+        };
+
+        TaskDefinition {
+            name: self.name.clone(),
+            overrides: self.overrides,
+            description: self.description.clone(),
+            cwd: self.cwd.clone(),
+            env: self
+                .env
+                .iter()
+                .map(|(name, value)| (name.clone(), value.substitute(values)))
+                .collect(),
+            env_files: self.env_files.clone(),
+            commands: self
+                .commands
+                .iter()
+                .map(|command| TaskCommand {
+                    command: command.command.substitute(values),
+                    conditions: command.conditions.clone(),
+                })
+                .collect(),
+            requires: self.requires.clone(),
+            after: self.after.clone(),
+            input_blocks: self
+                .input_blocks
+                .iter()
+                .map(|block| InputBlock {
+                    paths: substitute_all(&block.paths),
+                    env_vars: substitute_all(&block.env_vars),
+                    commands: substitute_all(&block.commands),
+                    conditions: block.conditions.clone(),
+                })
+                .collect(),
+            conditions: self.conditions.clone(),
+            matrix: self.matrix.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+impl TaskTemplate {
+    /// Whether `other` is a copy of this same template definition, as happens when
+    /// a file is imported more than once.
+    pub(in crate::config) fn is_same_definition(&self, other: &TaskTemplate) -> bool {
+        self.name.as_str() == other.name.as_str()
+            && self.name.span == other.name.span
+            && self.source.filename() == other.source.filename()
+    }
+}
+
+impl TaskDefinition {
+    /// Moves this task into `namespace`, along with any requirements on other
+    /// tasks in this project that are being moved into the same namespace.
+    pub(in crate::config) fn with_namespace(
+        mut self,
+        namespace: &str,
+        namespaced_tasks: &HashSet<String>,
+    ) -> TaskDefinition {
+        let namespaced =
+            |name: &Spanned<String>| format!("{namespace}.{}", name.as_str()).with_span(name.span);
+
+        self.name = namespaced(&self.name);
+        for requires in self.requires.iter_mut().chain(&mut self.after) {
+            let in_this_project = matches!(
+                requires.target.as_deref(),
+                None | Some(TargetSelector::CurrentProject)
+            );
+            if in_this_project && namespaced_tasks.contains(requires.task.as_str()) {
+                requires.task = namespaced(&requires.task);
+            }
+        }
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct TaskRequires {
     pub task: Spanned<String>,
     pub target: Option<Spanned<TargetSelector>>,
+    /// Whether it's fine for none of the targeted projects to have this task.
+    pub optional: bool,
+    /// Whether the target should follow dev dependencies between projects.
+    pub include_dev: bool,
+    /// This requirement is ignored unless all of these hold.
+    pub conditions: Vec<Condition>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+pub struct TaskCommand {
+    pub command: InterpolatedString,
+    /// This command is skipped unless all of these hold.
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug)]
 pub struct InputBlock {
-    pub paths: Vec<Glob>,
-    pub env_vars: Vec<String>,
-    pub commands: Vec<String>,
+    pub paths: Vec<InterpolatedString>,
+    pub env_vars: Vec<InterpolatedString>,
+    pub commands: Vec<InterpolatedString>,
+    /// This block is ignored unless all of these hold.
+    pub conditions: Vec<Condition>,
+}
+
+/// A `matrix` variable on a task, available to the task as `{matrix.<name>}`
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct MatrixVariable {
+    #[knuffel(argument)]
+    pub name: Spanned<String>,
+
+    #[knuffel(arguments)]
+    pub values: Vec<Spanned<String>>,
+}
+
+/// A `when` condition on the machine nabs is running on.
+///
+/// `os` is compared against the OS nabs was built for, e.g. linux, macos or windows.
+/// `env` is either the name of a variable that has to be set to something non-empty,
+/// or `NAME=value` to require a specific value.  If both are given both have to hold.
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct Condition {
+    #[knuffel(span)]
+    pub span: NodeSpan,
+
+    #[knuffel(property)]
+    pub os: Option<Spanned<String>>,
+
+    #[knuffel(property)]
+    pub env: Option<String>,
+}
+
+/// The values `std::env::consts::OS` can have, which are the only values `os` can match.
+pub const KNOWN_OSES: &[&str] = &[
+    "linux",
+    "macos",
+    "ios",
+    "tvos",
+    "watchos",
+    "visionos",
+    "freebsd",
+    "dragonfly",
+    "netbsd",
+    "openbsd",
+    "solaris",
+    "illumos",
+    "android",
+    "windows",
+    "fuchsia",
+    "haiku",
+    "redox",
+    "aix",
+    "hurd",
+    "emscripten",
+    "wasi",
+];
+
+impl Condition {
+    /// Explains why this condition doesn't hold on this machine, if it doesn't.
+    pub fn unmet_reason(&self) -> Option<String> {
+        if let Some(os) = &self.os {
+            if os.as_str() != std::env::consts::OS {
+                return Some(format!("os is {}, not {os}", std::env::consts::OS));
+            }
+        }
+
+        if let Some(env) = &self.env {
+            match env.split_once('=') {
+                Some((name, expected)) => {
+                    if std::env::var(name).ok().as_deref() != Some(expected) {
+                        return Some(format!("{name} isn't {expected}"));
+                    }
+                }
+                None => {
+                    if std::env::var(env).unwrap_or_default().is_empty() {
+                        return Some(format!("{env} isn't set"));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn holds(&self) -> bool {
+        self.unmet_reason().is_none()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum TargetSelector {
     CurrentProject,
     DependenciesOfCurrent,
+    TransitiveDependenciesOfCurrent,
+    DependentsOfCurrent,
+    /// Any dependency of the current project, direct or otherwise, with the given tag
+    Tagged(String),
     SpecificDependency(Spanned<SpecificProjectSelector>),
+    /// Everything matched by any of these selectors
+    Union(Vec<Spanned<TargetSelector>>),
 }
 
 #[derive(Clone, Debug)]
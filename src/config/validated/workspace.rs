@@ -1,7 +1,58 @@
-use super::super::Glob;
+use super::{
+    super::{
+        parsing::WorkspaceTasks,
+        spanned::{NodeSpan, Spanned},
+        Glob,
+    },
+    TaskDefinition,
+};
 
-#[derive(knuffel::Decode, Debug)]
+#[derive(Debug)]
 pub struct WorkspaceDefinition {
     pub name: String,
     pub project_paths: Vec<Glob>,
+    /// Environment variables that tasks can see when running with a hermetic environment.
+    pub env_passthrough: Vec<Glob>,
+    /// Tasks to add to projects in the workspace.
+    ///
+    /// These are validated separately for each project they're added to, as any
+    /// paths in the tasks are relative to that project.
+    pub(in crate::config) tasks: Vec<WorkspaceTasks>,
+    /// Tasks that belong to the workspace root rather than any project.
+    pub root_tasks: Vec<TaskDefinition>,
+    /// Rules restricting which projects can depend on each other.
+    pub rules: Vec<DependencyRule>,
+}
+
+/// A rule restricting the dependencies of the projects matching `from`
+/// (or every project if there's no `from`).
+///
+/// Dependencies matching any `deny` break the rule, as do dependencies that
+/// don't match any `allow` if there are some.
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct DependencyRule {
+    #[knuffel(argument)]
+    pub name: Spanned<String>,
+
+    #[knuffel(children(name = "from"))]
+    pub from: Vec<ProjectMatcher>,
+
+    #[knuffel(children(name = "allow"))]
+    pub allow: Vec<ProjectMatcher>,
+
+    #[knuffel(children(name = "deny"))]
+    pub deny: Vec<ProjectMatcher>,
+}
+
+/// Matches projects by path and/or tag.  If both are given a project has to match both.
+#[derive(knuffel::Decode, Debug, Clone)]
+pub struct ProjectMatcher {
+    #[knuffel(span)]
+    pub span: NodeSpan,
+
+    #[knuffel(property)]
+    pub projects: Option<Glob>,
+
+    #[knuffel(property)]
+    pub tag: Option<String>,
 }
@@ -11,10 +11,7 @@ use rayon::prelude::*;
 
 pub use registry::{HashRegistry, HashRegistryLoadError};
 
-use crate::{
-    config::ValidPath,
-    workspace::{ProjectInfo, TaskInfo},
-};
+use crate::{config::ValidPath, workspace::TaskInfo};
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default)]
 pub struct TaskHashes {
@@ -30,17 +27,28 @@ pub struct Hash([u8; 32]);
 pub enum HashError {
     #[error("Uncountered a path that wasn't UTF8: {0}")]
     InvalidPathFound(#[from] camino::FromPathBufError),
+    #[error("Couldn't read {0}: {1}")]
+    ReadingFile(Utf8PathBuf, std::io::Error),
 }
 
-pub fn hash_task_inputs(project: &ProjectInfo, task: &TaskInfo) -> Result<Option<Hash>, HashError> {
+/// Hashes everything that should cause `task` to re-run when it changes.
+///
+/// `extra_args` are any arguments passed through to the task from the command line.
+pub fn hash_task_inputs(
+    root: &ValidPath,
+    task: &TaskInfo,
+    extra_args: &[String],
+) -> Result<Option<Hash>, HashError> {
     if task.inputs.is_empty() {
         return Ok(None);
     }
 
     let mut hashes = Vec::with_capacity(task.inputs.len());
-    hash_file_inputs(&project.root, &task.inputs.paths, &mut hashes)?;
-    hash_env_vars(project, task, &mut hashes)?;
-    hash_commands(project, task, &mut hashes)?;
+    hash_file_inputs(root, &task.inputs.paths, &mut hashes)?;
+    hash_env_vars(task, &mut hashes)?;
+    hash_commands(task, &mut hashes)?;
+    hash_task_settings(task, extra_args, &mut hashes);
+    hash_env_files(task, &mut hashes)?;
     // TODO: also need to hash the task/project itself somehow...
 
     let mut hasher = blake3::Hasher::new();
@@ -101,19 +109,58 @@ fn hash_file_inputs(
     Ok(())
 }
 
-pub fn hash_env_vars(
-    _project: &ProjectInfo,
-    task: &TaskInfo,
-    #[allow(clippy::ptr_arg)] _hashes: &mut Vec<blake3::Hash>,
-) -> Result<(), HashError> {
-    if !task.inputs.env_vars.is_empty() {
-        todo!("Need to implement env var hashing")
+/// Hashes the settings a task is run with, so that changing them causes the task to re-run.
+fn hash_task_settings(task: &TaskInfo, extra_args: &[String], hashes: &mut Vec<blake3::Hash>) {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"cwd=");
+    hasher.update(task.cwd.as_subpath().as_str().as_bytes());
+    hasher.update(b"\0");
+    for (name, value) in &task.env {
+        hasher.update(b"env:");
+        hasher.update(name.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\0");
+    }
+    for arg in extra_args {
+        hasher.update(b"arg:");
+        hasher.update(arg.as_bytes());
+        hasher.update(b"\0");
+    }
+    hashes.push(hasher.finalize());
+}
+
+/// Hashes the contents of a tasks .env files, which are always treated as inputs.
+fn hash_env_files(task: &TaskInfo, hashes: &mut Vec<blake3::Hash>) -> Result<(), HashError> {
+    for path in &task.env_files {
+        let full_path = path.full_path();
+        let contents =
+            std::fs::read(&full_path).map_err(|e| HashError::ReadingFile(full_path, e))?;
+        hashes.push(blake3::hash(&contents));
+    }
+    Ok(())
+}
+
+pub fn hash_env_vars(task: &TaskInfo, hashes: &mut Vec<blake3::Hash>) -> Result<(), HashError> {
+    for name in &task.inputs.env_vars {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(name.as_bytes());
+        // Unset variables need to hash differently from empty ones
+        match std::env::var_os(name) {
+            Some(value) => {
+                hasher.update(b"=");
+                hasher.update(value.to_string_lossy().as_bytes());
+            }
+            None => {
+                hasher.update(b"\0");
+            }
+        }
+        hashes.push(hasher.finalize());
     }
     Ok(())
 }
 
 pub fn hash_commands(
-    _project: &ProjectInfo,
     task: &TaskInfo,
     #[allow(clippy::ptr_arg)] _hashes: &mut Vec<blake3::Hash>,
 ) -> Result<(), HashError> {
@@ -2,7 +2,7 @@ use std::{collections::HashMap, fs::File, sync::Mutex};
 
 use camino::Utf8PathBuf;
 
-use crate::workspace::{TaskRef, Workspace};
+use crate::workspace::{TaskOwner, TaskRef, Workspace};
 
 use super::{Hash, TaskHashes};
 
@@ -31,10 +31,11 @@ impl HashRegistry {
             RegistryFileFormat::V2 { hashes } => hashes
                 .into_iter()
                 .flat_map(|task_hashes| {
-                    let project = workspace.project_at_path(task_hashes.project)?;
-                    let task_ref = project
-                        .lookup_task(&task_hashes.task, workspace)?
-                        .task_ref();
+                    let owner = match task_hashes.project.as_str() {
+                        "//" => TaskOwner::WorkspaceRoot,
+                        path => TaskOwner::Project(workspace.project_at_path(path)?.project_ref()),
+                    };
+                    let task_ref = owner.lookup_task(&task_hashes.task, workspace)?.task_ref();
                     Some((task_ref, task_hashes.hahes))
                 })
                 .collect(),
@@ -63,7 +64,7 @@ impl HashRegistry {
             hashes: hashes
                 .into_iter()
                 .map(|(task_ref, hashes)| V2SerializableHashes {
-                    project: task_ref.project().as_str().to_owned(),
+                    project: task_ref.owner().to_string(),
                     task: task_ref.task_name().to_owned(),
                     hahes: hashes,
                 })
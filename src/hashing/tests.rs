@@ -63,3 +63,30 @@ mod hash_file_inputs {
         assert_eq!(first_hashes, second_hashes)
     }
 }
+
+mod hash_task_settings {
+    use crate::workspace::tests::a_workspace;
+
+    use super::*;
+
+    #[test]
+    fn test_extra_args_change_the_hash() {
+        let workspace = a_workspace();
+        let task = workspace
+            .project_at_path("projects/a-lib")
+            .unwrap()
+            .lookup_task("build", &workspace)
+            .unwrap();
+
+        let hash = |args: &[&str]| {
+            let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            let mut hashes = Vec::new();
+            hash_task_settings(task, &args, &mut hashes);
+            hashes
+        };
+
+        assert_eq!(hash(&["--foo"]), hash(&["--foo"]));
+        assert_ne!(hash(&["--foo"]), hash(&["--bar"]));
+        assert_ne!(hash(&[]), hash(&["--foo"]));
+    }
+}
@@ -1,12 +1,14 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use petgraph::{
-    algo::has_path_connecting,
+    algo::{has_path_connecting, tarjan_scc},
     stable_graph::NodeIndex,
     visit::{DfsPostOrder, EdgeFiltered, IntoNeighbors, Walker},
 };
 
-use super::{ProjectInfo, ProjectRef, TaskInfo, TaskRef};
+use crate::config::DependencyKind;
+
+use super::{ProjectInfo, ProjectRef, TaskInfo, TaskOwner, TaskRef};
 
 type Graph = petgraph::Graph<WorkspaceNode, WorkspaceEdge>;
 
@@ -42,21 +44,19 @@ impl WorkspaceGraph {
 
         for project_info in project_map.values() {
             // Create project dependency edges
-            for dependency in &project_info.dependencies {
+            for (dependency, kind) in &project_info.dependencies {
                 graph.add_edge(
                     self.project_indices[&project_info.project_ref()],
                     self.project_indices[dependency],
-                    WorkspaceEdge::ProjectDependsOn,
+                    WorkspaceEdge::ProjectDependsOn(*kind),
                 );
                 graph.add_edge(
                     self.project_indices[dependency],
                     self.project_indices[&project_info.project_ref()],
-                    WorkspaceEdge::ProjectDependedOnBy,
+                    WorkspaceEdge::ProjectDependedOnBy(*kind),
                 );
             }
         }
-
-        // TODO: Validate the graph to make sure there's no cycles?
     }
 
     pub(super) fn register_tasks(&mut self, task_map: &HashMap<TaskRef, TaskInfo>) {
@@ -64,11 +64,12 @@ impl WorkspaceGraph {
         for task in task_map.values() {
             let task_index = self.graph.add_node(WorkspaceNode::Task(task.task_ref()));
             self.task_indices.insert(task.task_ref(), task_index);
-            self.graph.add_edge(
-                self.project_indices[task.task_ref().project()],
-                task_index,
-                WorkspaceEdge::HasTask,
-            );
+            let owner_index = match task.task_ref().owner() {
+                TaskOwner::WorkspaceRoot => self.root_index,
+                TaskOwner::Project(project_ref) => self.project_indices[project_ref],
+            };
+            self.graph
+                .add_edge(owner_index, task_index, WorkspaceEdge::HasTask);
         }
     }
 
@@ -94,14 +95,112 @@ impl WorkspaceGraph {
         }
     }
 
+    /// Finds any cycles in the dependencies between projects
+    pub(super) fn project_cycles(&self) -> Vec<Vec<ProjectRef>> {
+        self.find_cycles(|edge| matches!(edge, WorkspaceEdge::ProjectDependsOn(_)))
+            .into_iter()
+            .map(|cycle| {
+                cycle
+                    .into_iter()
+                    .filter_map(|index| match &self.graph[index] {
+                        WorkspaceNode::Project(project_ref) => Some(project_ref.clone()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .map(start_at_smallest)
+            .collect()
+    }
+
+    /// Finds any cycles in the requirements between tasks
+    pub(super) fn task_cycles(&self) -> Vec<Vec<TaskRef>> {
+        self.find_cycles(|edge| {
+            matches!(
+                edge,
+                WorkspaceEdge::TaskDependsOn | WorkspaceEdge::TaskRunsAfter
+            )
+        })
+        .into_iter()
+        .map(|cycle| {
+            cycle
+                .into_iter()
+                .filter_map(|index| self.lookup_task(index))
+                .collect()
+        })
+        .map(start_at_smallest)
+        .collect()
+    }
+
+    /// Finds a cycle in each strongly connected component of the graph formed by
+    /// the edges matching `is_cycle_edge`.
+    ///
+    /// Each cycle is returned as the list of nodes in it, without repeating the first
+    /// node at the end.
+    fn find_cycles(&self, is_cycle_edge: fn(&WorkspaceEdge) -> bool) -> Vec<Vec<NodeIndex>> {
+        let filtered_graph =
+            EdgeFiltered::from_fn(&self.graph, |edge| is_cycle_edge(edge.weight()));
+
+        tarjan_scc(&filtered_graph)
+            .into_iter()
+            .filter_map(|component| {
+                let start = *component.iter().min()?;
+                let members = component.iter().copied().collect::<HashSet<_>>();
+
+                // Breadth first search within the component for the shortest path
+                // that leads back to where we started.
+                let mut parents = HashMap::new();
+                let mut queue = VecDeque::from([start]);
+                while let Some(index) = queue.pop_front() {
+                    for next in filtered_graph.neighbors(index) {
+                        if next == start {
+                            let mut cycle = vec![index];
+                            while let Some(parent) = parents.get(cycle.last()?) {
+                                cycle.push(*parent);
+                            }
+                            cycle.reverse();
+                            return Some(cycle);
+                        }
+                        if members.contains(&next) && !parents.contains_key(&next) {
+                            parents.insert(next, index);
+                            queue.push_back(next);
+                        }
+                    }
+                }
+                None
+            })
+            .collect()
+    }
+
+    pub(super) fn generate_ordering_edges(&mut self, orderings: &[(TaskRef, Vec<TaskRef>)]) {
+        for (task, predecessors) in orderings {
+            let current_task_index = self.task_indices[task];
+
+            for predecessor in predecessors {
+                let target_index = self.task_indices[predecessor];
+                if current_task_index != target_index {
+                    self.graph.add_edge(
+                        current_task_index,
+                        target_index,
+                        WorkspaceEdge::TaskRunsAfter,
+                    );
+                }
+            }
+        }
+    }
+
     pub fn dot(&self) -> petgraph::dot::Dot<'_, &Graph> {
         petgraph::dot::Dot::new(&self.graph)
     }
 
-    pub fn walk_project_dependents(&self, project: ProjectRef) -> HashSet<ProjectRef> {
-        let filtered_graph = EdgeFiltered::from_fn(&self.graph, |edge| {
-            matches!(edge.weight(), WorkspaceEdge::ProjectDependedOnBy)
-        });
+    pub fn walk_project_dependents(
+        &self,
+        project: ProjectRef,
+        dev: DevDependencies,
+    ) -> HashSet<ProjectRef> {
+        let filtered_graph = EdgeFiltered::from_fn(
+            &self.graph,
+            |edge| matches!(edge.weight(), WorkspaceEdge::ProjectDependedOnBy(kind) if dev.follows(*kind)),
+        );
 
         DfsPostOrder::new(&filtered_graph, self.project_indices[&project])
             .iter(&filtered_graph)
@@ -127,12 +226,21 @@ impl WorkspaceGraph {
     }
 
     pub fn project_tasks(&self, project_ref: &ProjectRef) -> Vec<TaskRef> {
+        self.owned_tasks(self.project_indices[project_ref])
+    }
+
+    /// The tasks attached directly to the workspace root
+    pub fn root_tasks(&self) -> Vec<TaskRef> {
+        self.owned_tasks(self.root_index)
+    }
+
+    fn owned_tasks(&self, owner_index: NodeIndex) -> Vec<TaskRef> {
         let filtered_graph = EdgeFiltered::from_fn(&self.graph, |edge| {
             matches!(edge.weight(), WorkspaceEdge::HasTask)
         });
 
         filtered_graph
-            .neighbors(self.project_indices[project_ref])
+            .neighbors(owner_index)
             .filter_map(|index| match &self.graph[index] {
                 WorkspaceNode::Task(task_ref) => Some(task_ref.clone()),
                 _ => None,
@@ -144,7 +252,10 @@ impl WorkspaceGraph {
         let filtered_graph = EdgeFiltered::from_fn(&self.graph, |edge| {
             matches!(
                 edge.weight(),
-                WorkspaceEdge::HasProject | WorkspaceEdge::HasTask | WorkspaceEdge::TaskDependsOn
+                WorkspaceEdge::HasProject
+                    | WorkspaceEdge::HasTask
+                    | WorkspaceEdge::TaskDependsOn
+                    | WorkspaceEdge::TaskRunsAfter
             )
         });
 
@@ -166,6 +277,19 @@ impl WorkspaceGraph {
     }
 }
 
+/// Rotates a cycle so it starts from its smallest element, giving us a consistent
+/// order regardless of the order nodes were added to the graph in.
+fn start_at_smallest<T: Ord>(mut cycle: Vec<T>) -> Vec<T> {
+    let smallest = cycle
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, item)| *item)
+        .map(|(position, _)| position)
+        .unwrap_or_default();
+    cycle.rotate_left(smallest);
+    cycle
+}
+
 impl TaskRef {
     pub fn direct_dependencies(&self, workspace: &super::Workspace) -> HashSet<TaskRef> {
         let graph = workspace.graph();
@@ -179,6 +303,20 @@ impl TaskRef {
             .filter_map(|index| graph.lookup_task(index))
             .collect()
     }
+
+    /// The tasks this one should run after, if they're also being run
+    pub fn runs_after(&self, workspace: &super::Workspace) -> HashSet<TaskRef> {
+        let graph = workspace.graph();
+
+        let filtered_graph = EdgeFiltered::from_fn(&graph.graph, |edge| {
+            matches!(edge.weight(), WorkspaceEdge::TaskRunsAfter)
+        });
+
+        filtered_graph
+            .neighbors(graph.task_indices[self])
+            .filter_map(|index| graph.lookup_task(index))
+            .collect()
+    }
 }
 
 impl ProjectInfo {
@@ -186,7 +324,7 @@ impl ProjectInfo {
         let graph = workspace.graph();
 
         let filtered_graph = EdgeFiltered::from_fn(&graph.graph, |edge| {
-            matches!(edge.weight(), WorkspaceEdge::ProjectDependsOn)
+            matches!(edge.weight(), WorkspaceEdge::ProjectDependsOn(_))
         });
 
         has_path_connecting(
@@ -197,20 +335,23 @@ impl ProjectInfo {
         )
     }
 
+    /// All the projects this one depends on, directly or otherwise
     // TODO: Consider calling this ancestors?  Not sure
-    #[allow(dead_code)]
-    pub fn dependencies<B>(&self, workspace: &super::Workspace) -> B
+    pub fn dependencies<B>(&self, workspace: &super::Workspace, dev: DevDependencies) -> B
     where
         B: FromIterator<ProjectRef>,
     {
         let graph = workspace.graph();
 
-        let filtered_graph = EdgeFiltered::from_fn(&graph.graph, |edge| {
-            matches!(edge.weight(), WorkspaceEdge::ProjectDependsOn)
-        });
+        let filtered_graph = EdgeFiltered::from_fn(
+            &graph.graph,
+            |edge| matches!(edge.weight(), WorkspaceEdge::ProjectDependsOn(kind) if dev.follows(*kind)),
+        );
 
-        DfsPostOrder::new(&filtered_graph, graph.project_indices[&self.project_ref()])
+        let start = graph.project_indices[&self.project_ref()];
+        DfsPostOrder::new(&filtered_graph, start)
             .iter(&filtered_graph)
+            .filter(|index| *index != start)
             .filter_map(|index| match &graph.graph[index] {
                 WorkspaceNode::Project(project_ref) => Some(project_ref.clone()),
                 WorkspaceNode::Task(_) | WorkspaceNode::WorkspaceRoot => None,
@@ -218,15 +359,36 @@ impl ProjectInfo {
             .collect()
     }
 
-    pub fn direct_dependencies<B>(&self, workspace: &super::Workspace) -> B
+    pub fn direct_dependents<B>(&self, workspace: &super::Workspace, dev: DevDependencies) -> B
     where
         B: FromIterator<ProjectRef>,
     {
         let graph = workspace.graph();
 
-        let filtered_graph = EdgeFiltered::from_fn(&graph.graph, |edge| {
-            matches!(edge.weight(), WorkspaceEdge::ProjectDependsOn)
-        });
+        let filtered_graph = EdgeFiltered::from_fn(
+            &graph.graph,
+            |edge| matches!(edge.weight(), WorkspaceEdge::ProjectDependedOnBy(kind) if dev.follows(*kind)),
+        );
+
+        filtered_graph
+            .neighbors(graph.project_indices[&self.project_ref()])
+            .filter_map(|index| match &graph.graph[index] {
+                WorkspaceNode::Project(project_ref) => Some(project_ref.clone()),
+                WorkspaceNode::Task(_) | WorkspaceNode::WorkspaceRoot => None,
+            })
+            .collect()
+    }
+
+    pub fn direct_dependencies<B>(&self, workspace: &super::Workspace, dev: DevDependencies) -> B
+    where
+        B: FromIterator<ProjectRef>,
+    {
+        let graph = workspace.graph();
+
+        let filtered_graph = EdgeFiltered::from_fn(
+            &graph.graph,
+            |edge| matches!(edge.weight(), WorkspaceEdge::ProjectDependsOn(kind) if dev.follows(*kind)),
+        );
 
         filtered_graph
             .neighbors(graph.project_indices[&self.project_ref()])
@@ -238,6 +400,19 @@ impl ProjectInfo {
     }
 }
 
+/// Whether walks over project dependencies should follow dev dependencies
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DevDependencies {
+    Include,
+    Exclude,
+}
+
+impl DevDependencies {
+    fn follows(self, kind: DependencyKind) -> bool {
+        self == DevDependencies::Include || kind != DependencyKind::Dev
+    }
+}
+
 #[derive(Debug)]
 pub enum WorkspaceNode {
     WorkspaceRoot,
@@ -248,10 +423,12 @@ pub enum WorkspaceNode {
 #[derive(Debug)]
 pub enum WorkspaceEdge {
     HasProject,
-    ProjectDependsOn,
-    ProjectDependedOnBy,
+    ProjectDependsOn(DependencyKind),
+    ProjectDependedOnBy(DependencyKind),
     TaskDependsOn,
     TaskDependedOnBy,
+    /// The task should run after another, but only if they're both being run.
+    TaskRunsAfter,
     HasTask,
 }
 
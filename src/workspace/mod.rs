@@ -1,20 +1,23 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use miette::SourceSpan;
 
 use crate::{
     config::{
-        self, ConfigSource, SpecificProjectSelector, TargetSelector, ValidPath, WorkspaceRoot,
+        self, ConfigSource, DependencyKind, InterpolationContext, SpecificProjectSelector,
+        TargetSelector, ValidPath, WorkspaceRoot,
     },
-    diagnostics::{CollectResults, ConfigError, DynDiagnostic},
+    diagnostics::{ConfigError, DynDiagnostic},
 };
 
-use self::graph::WorkspaceGraph;
+pub use self::graph::DevDependencies;
+use self::{graph::WorkspaceGraph, rules::DependencyRules};
 
 mod graph;
+mod rules;
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
 
 use camino::Utf8Path;
 use globset::Glob;
@@ -25,6 +28,11 @@ pub struct Workspace {
     project_map: HashMap<ProjectRef, ProjectInfo>,
     task_map: HashMap<TaskRef, TaskInfo>,
     task_requirements: Vec<(TaskRef, Vec<TaskRef>)>,
+    /// Tasks that should run before others, but only if they're already being run.
+    task_orderings: Vec<(TaskRef, Vec<TaskRef>)>,
+    /// Tasks owned by the workspace root, which are added alongside the projects.
+    root_task_definitions: Vec<config::TaskDefinition>,
+    dependency_rules: DependencyRules,
 }
 
 impl std::fmt::Debug for Workspace {
@@ -46,6 +54,7 @@ impl std::fmt::Debug for Workspace {
             .field("project_map", &project_map)
             .field("task_map", &task_map)
             .field("task_requirements", &self.task_requirements)
+            .field("task_orderings", &self.task_orderings)
             .finish_non_exhaustive()
     }
 }
@@ -55,6 +64,7 @@ pub struct WorkspaceInfo {
     #[allow(unused)]
     name: String,
     pub project_paths: Vec<Glob>,
+    pub env_passthrough: Vec<Glob>,
     pub root_path: WorkspaceRoot,
 }
 
@@ -68,6 +78,12 @@ impl Workspace {
                 .into_iter()
                 .map(|g| g.into_inner())
                 .collect(),
+            env_passthrough: workspace_file
+                .config
+                .env_passthrough
+                .into_iter()
+                .map(|g| g.into_inner())
+                .collect(),
             root_path: workspace_file.workspace_root,
         };
 
@@ -77,6 +93,12 @@ impl Workspace {
             project_map: HashMap::new(),
             task_map: HashMap::new(),
             task_requirements: Vec::new(),
+            task_orderings: Vec::new(),
+            root_task_definitions: workspace_file.config.root_tasks,
+            dependency_rules: DependencyRules::new(
+                workspace_file.config.rules,
+                workspace_file.source,
+            ),
         }
     }
 
@@ -93,12 +115,16 @@ impl Workspace {
 
         let mut tasks_to_process = Vec::new();
         let mut errors = Vec::new();
+        // Where each dependency & requirement was declared, for reporting cycles
+        let mut dependency_spans = HashMap::new();
+        let mut edge_spans = HashMap::new();
 
         'outer: for project_file in project_files {
             let project_ref = ProjectRef(project_file.project_root.clone());
 
             let mut dependencies = Vec::new();
-            for path in project_file.config.dependencies {
+            for dependency in project_file.config.dependencies {
+                let path = dependency.path;
                 if !project_paths.contains(&path) {
                     errors.push(DynDiagnostic::new(UnknownProjectError {
                         span: path.span,
@@ -107,34 +133,71 @@ impl Workspace {
                     }));
                     continue 'outer;
                 }
-                dependencies.push(ProjectRef(path.into_inner()));
+                let dependency_kind = dependency.kind;
+                let dependency = ProjectRef(path.clone().into_inner());
+                dependency_spans.insert(
+                    (project_ref.clone(), dependency.clone()),
+                    (path.span, project_file.source.clone()),
+                );
+                dependencies.push((dependency, dependency_kind));
             }
 
             for task in project_file.config.tasks.tasks {
-                let task_ref = TaskRef(project_ref.clone(), task.name.clone());
-                self.task_map.insert(
-                    task_ref.clone(),
-                    TaskInfo {
-                        project_name: project_file.config.project.clone(),
-                        project: project_ref.clone(),
-                        name: task.name,
-                        commands: task.commands,
-                        inputs: TaskInputs::from_config(&task.input_blocks),
-                    },
-                );
-                tasks_to_process.push((task_ref, task.requires, task.source));
+                let task_infos = match self.task_infos(
+                    TaskOwner::Project(project_ref.clone()),
+                    &project_file.config.project,
+                    &project_file.project_root,
+                    &project_file.config.env_files,
+                    &task,
+                ) {
+                    Ok(task_infos) => task_infos,
+                    Err(error) => {
+                        errors.push(error);
+                        continue;
+                    }
+                };
+                for task_info in task_infos {
+                    let task_ref = task_info.task_ref();
+                    self.task_map.insert(task_ref.clone(), task_info);
+                    tasks_to_process.push((
+                        task_ref,
+                        task.requires.clone(),
+                        task.after.clone(),
+                        task.source.clone(),
+                    ));
+                }
             }
 
             self.project_map.insert(
                 project_ref.clone(),
                 ProjectInfo {
-                    name: project_file.config.project.clone(),
+                    name: project_file.config.project.into_inner(),
+                    tags: project_file.config.tags,
                     dependencies,
                     root: project_file.project_root,
                 },
             );
         }
 
+        let workspace_root = ValidPath::from(self.info.root_path.clone());
+        for task in std::mem::take(&mut self.root_task_definitions) {
+            match self.task_infos(TaskOwner::WorkspaceRoot, "//", &workspace_root, &[], &task) {
+                Ok(task_infos) => {
+                    for task_info in task_infos {
+                        let task_ref = task_info.task_ref();
+                        self.task_map.insert(task_ref.clone(), task_info);
+                        tasks_to_process.push((
+                            task_ref,
+                            task.requires.clone(),
+                            task.after.clone(),
+                            task.source.clone(),
+                        ));
+                    }
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+
         if !errors.is_empty() {
             return Err(ConfigError { errors });
         }
@@ -142,23 +205,50 @@ impl Workspace {
         self.graph_.add_projects(&self.project_map);
         self.graph_.register_tasks(&self.task_map);
 
-        let mut errors = Vec::new();
-        for (task_ref, requires, source) in tasks_to_process {
-            let project = task_ref.project().lookup(self);
-            let requires = requires
-                .into_iter()
-                .map(|requires| resolve_requires(requires, project, self, &source))
-                .collect_results();
-
-            match requires {
-                Ok(requires) => self
-                    .task_requirements
-                    .push((task_ref, requires.into_iter().flatten().collect())),
-                Err(errs) => errors.extend(
-                    errs.into_iter()
-                        .map(|e| DynDiagnostic::new(e).with_source_code(source.clone())),
-                ),
-            }
+        let mut errors = self
+            .graph_
+            .project_cycles()
+            .into_iter()
+            .map(|cycle| {
+                let edges = cycle_edges(&cycle)
+                    .map(|(from, to)| {
+                        let (span, source) = &dependency_spans[&(from.clone(), to.clone())];
+                        DynDiagnostic::new(CyclicDependency {
+                            from: from.lookup(self).name.clone(),
+                            to: to.lookup(self).name.clone(),
+                            span: *span,
+                        })
+                        .with_source_code(source.clone())
+                    })
+                    .collect();
+                DynDiagnostic::new(ProjectCycleError {
+                    cycle: cycle_names(cycle.iter().map(|p| p.lookup(self).name.clone())),
+                    dependencies: edges,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        errors.extend(self.dependency_rules.check(self, &dependency_spans));
+
+        for (task_ref, requires, after, source) in tasks_to_process {
+            let required = self.resolve_task_edges(
+                &task_ref,
+                requires,
+                "requires",
+                &source,
+                &mut edge_spans,
+                &mut errors,
+            );
+            let after = self.resolve_task_edges(
+                &task_ref,
+                after,
+                "runs after",
+                &source,
+                &mut edge_spans,
+                &mut errors,
+            );
+            self.task_requirements.push((task_ref.clone(), required));
+            self.task_orderings.push((task_ref, after));
         }
 
         if !errors.is_empty() {
@@ -166,10 +256,158 @@ impl Workspace {
         }
 
         self.graph_.generate_task_edges(&self.task_requirements);
+        self.graph_.generate_ordering_edges(&self.task_orderings);
+
+        let errors = self
+            .graph_
+            .task_cycles()
+            .into_iter()
+            .map(|cycle| {
+                let edges = cycle_edges(&cycle)
+                    .map(|(from, to)| {
+                        let (span, source, relation) = &edge_spans[&(from.clone(), to.clone())];
+                        DynDiagnostic::new(CyclicRequirement {
+                            from: from.to_string(),
+                            relation,
+                            to: to.to_string(),
+                            span: *span,
+                        })
+                        .with_source_code(source.clone())
+                    })
+                    .collect();
+                DynDiagnostic::new(TaskCycleError {
+                    cycle: cycle_names(cycle.iter().map(|t| t.to_string())),
+                    requirements: edges,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !errors.is_empty() {
+            return Err(ConfigError { errors });
+        }
 
         Ok(())
     }
 
+    /// Builds the TaskInfos for a task owned by the project (or workspace root) at `root`
+    ///
+    /// There's one of these for each combination of the tasks matrix values, or just
+    /// the one if the task doesn't have a matrix.
+    fn task_infos(
+        &self,
+        owner: TaskOwner,
+        owner_name: &str,
+        root: &ValidPath,
+        env_files: &[ValidPath],
+        task: &config::TaskDefinition,
+    ) -> Result<Vec<TaskInfo>, DynDiagnostic> {
+        matrix_instances(task)
+            .into_iter()
+            .map(|instance| {
+                self.task_info(owner.clone(), owner_name, root, env_files, task, instance)
+            })
+            .collect()
+    }
+
+    fn task_info(
+        &self,
+        owner: TaskOwner,
+        owner_name: &str,
+        root: &ValidPath,
+        env_files: &[ValidPath],
+        task: &config::TaskDefinition,
+        instance: MatrixInstance,
+    ) -> Result<TaskInfo, DynDiagnostic> {
+        let MatrixInstance { name, matrix } = instance;
+        let root_path = root.full_path();
+        let context = InterpolationContext {
+            project_name: owner_name,
+            project_root: &root_path,
+            workspace_root: self.info.root_path.as_ref(),
+            task_name: &name,
+            matrix: &matrix,
+        };
+        let input_blocks = task
+            .input_blocks
+            .iter()
+            .filter(|block| all_hold(&block.conditions))
+            .cloned()
+            .collect::<Vec<_>>();
+        let inputs = TaskInputs::from_config(&input_blocks, &context)
+            .map_err(|error| DynDiagnostic::new(error).with_source_code(task.source.clone()))?;
+
+        let commands = task
+            .commands
+            .iter()
+            .filter(|c| all_hold(&c.conditions))
+            .map(|c| c.command.render(&context))
+            .collect::<Vec<_>>();
+
+        Ok(TaskInfo {
+            project_name: owner_name.to_owned(),
+            owner,
+            alias: task.commands.is_empty(),
+            commands,
+            env: task
+                .env
+                .iter()
+                .map(|(name, value)| (name.clone(), value.render(&context)))
+                .collect(),
+            cwd: task.cwd.clone().unwrap_or_else(|| root.clone()),
+            env_files: env_files.iter().chain(&task.env_files).cloned().collect(),
+            name,
+            matrix,
+            description: task.description.clone(),
+            defined_in: task.source.filename().to_owned(),
+            inputs,
+            disabled: task
+                .conditions
+                .iter()
+                .find_map(config::Condition::unmet_reason),
+        })
+    }
+
+    /// Resolves the `requires` or `after` statements of a task into the tasks they refer to.
+    ///
+    /// Where each resulting edge was declared is recorded in `edge_spans` so we can
+    /// point at it if the edge turns out to be part of a cycle.
+    fn resolve_task_edges(
+        &self,
+        task_ref: &TaskRef,
+        statements: Vec<config::TaskRequires>,
+        relation: &'static str,
+        source: &ConfigSource,
+        edge_spans: &mut HashMap<(TaskRef, TaskRef), (SourceSpan, ConfigSource, &'static str)>,
+        errors: &mut Vec<DynDiagnostic>,
+    ) -> Vec<TaskRef> {
+        if task_ref.lookup(self).disabled.is_some() {
+            return Vec::new();
+        }
+
+        let mut tasks = Vec::new();
+        for statement in statements {
+            if !all_hold(&statement.conditions) {
+                continue;
+            }
+            let span = statement.task.span;
+            let matrix = &task_ref.lookup(self).matrix;
+            match resolve_requires(statement, task_ref.owner(), matrix, self, source) {
+                Ok(resolved) => {
+                    for task in resolved {
+                        edge_spans
+                            .entry((task_ref.clone(), task.clone()))
+                            .or_insert_with(|| (span, source.clone(), relation));
+                        tasks.push(task);
+                    }
+                }
+                Err(error) => {
+                    errors.push(DynDiagnostic::new(error).with_source_code(source.clone()))
+                }
+            }
+        }
+        tasks
+    }
+
     pub fn graph(&self) -> &WorkspaceGraph {
         &self.graph_
     }
@@ -188,6 +426,19 @@ impl Workspace {
         self.project_map.values().find(|p| p.name == name)
     }
 
+    /// The tasks owned by the workspace root, rather than any project.
+    pub fn root_tasks(&self) -> Vec<&TaskInfo> {
+        self.graph()
+            .root_tasks()
+            .into_iter()
+            .map(|r| r.lookup(self))
+            .collect()
+    }
+
+    pub fn root_task(&self, name: &str) -> Option<&TaskInfo> {
+        self.root_tasks().into_iter().find(|task| task.name == name)
+    }
+
     pub fn root_path(&self) -> &WorkspaceRoot {
         &self.info.root_path
     }
@@ -202,6 +453,16 @@ impl Workspace {
         }
         builder.build().unwrap()
     }
+
+    /// A GlobSet matching the names of environment variables that should be passed
+    /// through to tasks running with a hermetic environment.
+    pub fn env_passthrough_globset(&self) -> globset::GlobSet {
+        let mut builder = globset::GlobSetBuilder::new();
+        for glob in &self.info.env_passthrough {
+            builder.add(glob.clone());
+        }
+        builder.build().unwrap()
+    }
 }
 
 #[derive(thiserror::Error, miette::Diagnostic, Debug)]
@@ -216,6 +477,57 @@ struct UnknownProjectError {
     source_code: ConfigSource,
 }
 
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("Projects depend on each other in a cycle: {}", cycle.join(" → "))]
+#[diagnostic(help("remove one of these dependencies to break the cycle"))]
+struct ProjectCycleError {
+    cycle: Vec<String>,
+    #[related]
+    dependencies: Vec<DynDiagnostic>,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{from} depends on {to} here")]
+struct CyclicDependency {
+    from: String,
+    to: String,
+    #[label("this dependency is part of the cycle")]
+    span: SourceSpan,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("Tasks require each other in a cycle: {}", cycle.join(" → "))]
+#[diagnostic(help("remove one of these requires to break the cycle"))]
+struct TaskCycleError {
+    cycle: Vec<String>,
+    #[related]
+    requirements: Vec<DynDiagnostic>,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{from} {relation} {to} here")]
+struct CyclicRequirement {
+    from: String,
+    relation: &'static str,
+    to: String,
+    #[label("this requirement is part of the cycle")]
+    span: SourceSpan,
+}
+
+/// Each pair of adjacent items in a cycle, including the one that closes it.
+fn cycle_edges<T>(cycle: &[T]) -> impl Iterator<Item = (&T, &T)> {
+    cycle.iter().zip(cycle.iter().cycle().skip(1))
+}
+
+/// The names of the items in a cycle, with the first repeated at the end to close it.
+fn cycle_names(names: impl Iterator<Item = String>) -> Vec<String> {
+    let mut names = names.collect::<Vec<_>>();
+    if let Some(first) = names.first().cloned() {
+        names.push(first);
+    }
+    names
+}
+
 impl ProjectRef {
     pub fn lookup<'a>(&self, workspace: &'a Workspace) -> &'a ProjectInfo {
         &workspace.project_map[self]
@@ -234,7 +546,8 @@ impl TaskRef {
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub struct ProjectInfo {
     pub name: String,
-    pub dependencies: Vec<ProjectRef>,
+    pub tags: Vec<String>,
+    pub dependencies: Vec<(ProjectRef, DependencyKind)>,
     pub root: ValidPath,
 }
 
@@ -268,11 +581,53 @@ impl ProjectRef {
     }
 }
 
+/// The thing a task belongs to: either a project or the workspace itself.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub struct TaskRef(ProjectRef, String);
+pub enum TaskOwner {
+    WorkspaceRoot,
+    Project(ProjectRef),
+}
+
+impl TaskOwner {
+    /// The directory this owner lives in
+    pub fn root(&self, workspace: &Workspace) -> ValidPath {
+        match self {
+            TaskOwner::WorkspaceRoot => ValidPath::from(workspace.root_path().clone()),
+            TaskOwner::Project(project_ref) => project_ref.lookup(workspace).root.clone(),
+        }
+    }
+
+    pub fn tasks<'a>(&self, workspace: &'a Workspace) -> Vec<&'a TaskInfo> {
+        match self {
+            TaskOwner::WorkspaceRoot => workspace.root_tasks(),
+            TaskOwner::Project(project_ref) => project_ref.lookup(workspace).tasks(workspace),
+        }
+    }
+
+    pub fn lookup_task<'a>(&self, name: &str, workspace: &'a Workspace) -> Option<&'a TaskInfo> {
+        match self {
+            TaskOwner::WorkspaceRoot => workspace.root_task(name),
+            TaskOwner::Project(project_ref) => {
+                project_ref.lookup(workspace).lookup_task(name, workspace)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for TaskOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskOwner::WorkspaceRoot => write!(f, "//"),
+            TaskOwner::Project(project_ref) => write!(f, "{}", project_ref.as_str()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskRef(TaskOwner, String);
 
 impl TaskRef {
-    pub fn project(&self) -> &ProjectRef {
+    pub fn owner(&self) -> &TaskOwner {
         &self.0
     }
 
@@ -283,25 +638,110 @@ impl TaskRef {
 
 impl std::fmt::Display for TaskRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}::{}", self.0.as_str(), self.task_name())
+        write!(f, "{}::{}", self.0, self.task_name())
     }
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct TaskInfo {
-    pub project: ProjectRef,
+    pub owner: TaskOwner,
     pub project_name: String,
     pub name: String,
+    pub description: Option<String>,
     pub commands: Vec<String>,
     pub inputs: TaskInputs,
+    pub env: BTreeMap<String, String>,
+    /// .env files to load into the environment before running this task.
+    ///
+    /// These are applied in order, before `env`.
+    pub env_files: Vec<ValidPath>,
+    /// The directory this tasks commands should be run in.
+    pub cwd: ValidPath,
+    /// Why this task is disabled, if one of its `when` conditions doesn't hold.
+    ///
+    /// Disabled tasks are skipped when run and don't require any other tasks.
+    pub disabled: Option<String>,
+    /// The matrix values this instance of the task runs with, if it has a matrix.
+    pub matrix: BTreeMap<String, String>,
+    /// Whether this task was declared without any commands, and just groups the tasks it requires.
+    ///
+    /// Tasks without any commands are never hashed or run, their outcome comes from their
+    /// requirements.  This is also true of tasks whose commands are all filtered out by `when`.
+    pub alias: bool,
+    /// The config file this task was defined in, relative to the workspace root.
+    pub defined_in: String,
 }
 
 impl TaskInfo {
     pub fn task_ref(&self) -> TaskRef {
-        TaskRef(self.project.clone(), self.name.clone())
+        TaskRef(self.owner.clone(), self.name.clone())
+    }
+
+    /// The name of the task this is an instance of, without any matrix values.
+    pub fn base_name(&self) -> &str {
+        match self.name.split_once('[') {
+            Some((base_name, _)) if !self.matrix.is_empty() => base_name,
+            _ => &self.name,
+        }
+    }
+
+    /// Whether this task is called `name`, or is an instance of a matrix task called `name`.
+    pub fn answers_to(&self, name: &str) -> bool {
+        self.name == name || self.base_name() == name
+    }
+
+    /// Whether a requirement on `name` from a task running with `matrix` should match this task.
+    ///
+    /// Instances of matrix tasks only match if they agree on the values of any matrix
+    /// variables the two tasks share.
+    fn satisfies(&self, name: &str, matrix: &BTreeMap<String, String>) -> bool {
+        self.name == name
+            || (self.base_name() == name
+                && self
+                    .matrix
+                    .iter()
+                    .all(|(variable, value)| matrix.get(variable).is_none_or(|v| v == value)))
     }
 }
 
+/// One instance of a task, with the name and matrix values it runs with
+struct MatrixInstance {
+    name: String,
+    matrix: BTreeMap<String, String>,
+}
+
+/// Every instance of `task`, one for each combination of its matrix values
+fn matrix_instances(task: &config::TaskDefinition) -> Vec<MatrixInstance> {
+    let mut instances = vec![Vec::<(&str, &str)>::new()];
+    for variable in &task.matrix {
+        instances = instances
+            .into_iter()
+            .flat_map(|instance| {
+                variable.values.iter().map(move |value| {
+                    let mut instance = instance.clone();
+                    instance.push((variable.name.as_str(), value.as_ref().as_str()));
+                    instance
+                })
+            })
+            .collect();
+    }
+
+    instances
+        .into_iter()
+        .map(|values| {
+            let name = values
+                .iter()
+                .map(|(variable, value)| format!("[{variable}={value}]"))
+                .fold(task.name.to_string(), |name, suffix| name + &suffix);
+            let matrix = values
+                .into_iter()
+                .map(|(variable, value)| (variable.to_owned(), value.to_owned()))
+                .collect();
+            MatrixInstance { name, matrix }
+        })
+        .collect()
+}
+
 #[derive(thiserror::Error, miette::Diagnostic, Debug)]
 enum TaskResolutionError {
     #[diagnostic()]
@@ -382,7 +822,8 @@ impl std::fmt::Display for TargetPronoun {
 
 fn resolve_requires(
     requires: config::TaskRequires,
-    current_project: &ProjectInfo,
+    current_owner: &TaskOwner,
+    current_matrix: &BTreeMap<String, String>,
     workspace: &Workspace,
     source: &ConfigSource,
 ) -> Result<Vec<TaskRef>, TaskResolutionError> {
@@ -391,51 +832,35 @@ fn resolve_requires(
         None => &TargetSelector::CurrentProject,
     };
 
-    let projects = match target {
-        TargetSelector::CurrentProject => vec![current_project],
-        TargetSelector::DependenciesOfCurrent => current_project
-            .direct_dependencies::<Vec<_>>(workspace)
-            .into_iter()
-            .map(|project| project.lookup(workspace))
-            .collect(),
-        TargetSelector::SpecificDependency(selector) => {
-            let project =
-                match selector.as_ref() {
-                    SpecificProjectSelector::ByName(name) => workspace
-                        .project_by_name(name.as_str())
-                        .ok_or_else(|| TaskResolutionError::UnknownProjectByName {
-                            name: name.to_string(),
-                            span: selector.span,
-                            source_code: source.clone(),
-                        })?,
-                    SpecificProjectSelector::ByPath(path) => workspace
-                        .project_at_path(path.full_path())
-                        .ok_or_else(|| TaskResolutionError::UnknownProjectByPath {
-                            path: path.clone(),
-                            span: selector.span,
-                            source_code: source.clone(),
-                        })?,
-                };
-
-            if !current_project.has_dependency(&project.project_ref(), workspace) {
-                return Err(TaskResolutionError::RequiredFromUnrelatedProject {
-                    required_project: project.name.clone(),
-                    current_project: current_project.name.clone(),
-                    span: selector.span,
-                    source_code: source.clone(),
-                });
-            }
-            vec![project]
-        }
+    // Tasks owned by the workspace root can require tasks from any project
+    let current_project = match current_owner {
+        TaskOwner::WorkspaceRoot => None,
+        TaskOwner::Project(project_ref) => Some(project_ref.lookup(workspace)),
     };
 
-    let tasks = projects
-        .into_iter()
-        .flat_map(|project| project.lookup_task(&requires.task, workspace))
+    let dev = match requires.include_dev {
+        true => DevDependencies::Include,
+        false => DevDependencies::Exclude,
+    };
+    let owners = selected_owners(
+        target,
+        current_owner,
+        current_project,
+        dev,
+        workspace,
+        source,
+    )?;
+
+    // owner.tasks iterates a HashMap, so sort to keep the requirements stable between runs
+    let mut tasks = owners
+        .iter()
+        .flat_map(|owner| owner.tasks(workspace))
+        .filter(|task| task.satisfies(&requires.task, current_matrix))
         .map(|task| task.task_ref())
         .collect::<Vec<_>>();
+    tasks.sort();
 
-    if tasks.is_empty() {
+    if tasks.is_empty() && !requires.optional {
         return match &requires.target {
             Some(target) => Err(TaskResolutionError::NoMatchingTasks {
                 task_name_span: requires.task.span,
@@ -445,14 +870,21 @@ fn resolve_requires(
                     TargetSelector::CurrentProject | TargetSelector::SpecificDependency(_) => {
                         TargetPronoun::This
                     }
-                    TargetSelector::DependenciesOfCurrent => TargetPronoun::These,
+                    TargetSelector::DependenciesOfCurrent
+                    | TargetSelector::TransitiveDependenciesOfCurrent
+                    | TargetSelector::DependentsOfCurrent
+                    | TargetSelector::Tagged(_)
+                    | TargetSelector::Union(_) => TargetPronoun::These,
                 },
                 source_code: source.clone(),
             }),
             None => Err(TaskResolutionError::NoMatchingTasksForImplicitSelf {
                 task_name_span: requires.task.span,
                 task_name: requires.task.as_str().to_owned(),
-                current_project: current_project.name.clone(),
+                current_project: match current_project {
+                    Some(project) => project.name.clone(),
+                    None => "the workspace root".to_owned(),
+                },
                 source_code: source.clone(),
             }),
         };
@@ -461,6 +893,107 @@ fn resolve_requires(
     Ok(tasks)
 }
 
+/// Finds the owners of the tasks that a requires target selects
+fn selected_owners(
+    target: &TargetSelector,
+    current_owner: &TaskOwner,
+    current_project: Option<&ProjectInfo>,
+    dev: DevDependencies,
+    workspace: &Workspace,
+    source: &ConfigSource,
+) -> Result<Vec<TaskOwner>, TaskResolutionError> {
+    let projects: Vec<ProjectRef> = match target {
+        TargetSelector::CurrentProject => return Ok(vec![current_owner.clone()]),
+        TargetSelector::DependenciesOfCurrent => match current_project {
+            Some(current_project) => current_project.direct_dependencies(workspace, dev),
+            None => workspace.projects().map(ProjectInfo::project_ref).collect(),
+        },
+        TargetSelector::TransitiveDependenciesOfCurrent => match current_project {
+            Some(current_project) => current_project.dependencies(workspace, dev),
+            None => workspace.projects().map(ProjectInfo::project_ref).collect(),
+        },
+        TargetSelector::DependentsOfCurrent => match current_project {
+            Some(current_project) => current_project.direct_dependents(workspace, dev),
+            // Nothing can depend on the workspace root
+            None => vec![],
+        },
+        TargetSelector::Tagged(tag) => {
+            let candidates: Vec<ProjectRef> = match current_project {
+                Some(current_project) => current_project.dependencies(workspace, dev),
+                None => workspace.projects().map(ProjectInfo::project_ref).collect(),
+            };
+            candidates
+                .into_iter()
+                .filter(|project| project.lookup(workspace).tags.contains(tag))
+                .collect()
+        }
+        TargetSelector::SpecificDependency(selector) => {
+            let project =
+                match selector.as_ref() {
+                    SpecificProjectSelector::ByName(name) => workspace
+                        .project_by_name(name.as_str())
+                        .ok_or_else(|| TaskResolutionError::UnknownProjectByName {
+                            name: name.to_string(),
+                            span: selector.span,
+                            source_code: source.clone(),
+                        })?,
+                    SpecificProjectSelector::ByPath(path) => workspace
+                        .project_at_path(path.full_path())
+                        .ok_or_else(|| TaskResolutionError::UnknownProjectByPath {
+                            path: path.clone(),
+                            span: selector.span,
+                            source_code: source.clone(),
+                        })?,
+                };
+
+            if let Some(current_project) = current_project {
+                if !current_project.has_dependency(&project.project_ref(), workspace) {
+                    return Err(TaskResolutionError::RequiredFromUnrelatedProject {
+                        required_project: project.name.clone(),
+                        current_project: current_project.name.clone(),
+                        span: selector.span,
+                        source_code: source.clone(),
+                    });
+                }
+            }
+            vec![project.project_ref()]
+        }
+        TargetSelector::Union(selectors) => {
+            let mut owners = Vec::new();
+            for selector in selectors {
+                for owner in selected_owners(
+                    selector,
+                    current_owner,
+                    current_project,
+                    dev,
+                    workspace,
+                    source,
+                )? {
+                    if !owners.contains(&owner) {
+                        owners.push(owner);
+                    }
+                }
+            }
+            return Ok(owners);
+        }
+    };
+
+    Ok(projects.into_iter().map(TaskOwner::Project).collect())
+}
+
+/// Whether all of the `when` conditions on something hold on this machine
+fn all_hold(conditions: &[config::Condition]) -> bool {
+    conditions.iter().all(config::Condition::holds)
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("Couldn't parse an input path: {error}")]
+pub struct InvalidInputPath {
+    #[label = "this path isn't a valid glob"]
+    span: SourceSpan,
+    error: globset::Error,
+}
+
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
 pub struct TaskInputs {
     pub paths: Vec<Glob>,
@@ -477,27 +1010,39 @@ impl TaskInputs {
         self.paths.len() + self.env_vars.len() + self.commands.len()
     }
 
-    pub fn from_config(inputs: &[config::InputBlock]) -> TaskInputs {
+    pub fn from_config(
+        inputs: &[config::InputBlock],
+        context: &InterpolationContext<'_>,
+    ) -> Result<TaskInputs, InvalidInputPath> {
         let mut this = TaskInputs::default();
         for input in inputs {
-            this.load_block(input)
+            this.load_block(input, context)?
         }
-        this
+        Ok(this)
     }
 
-    fn load_block(&mut self, inputs: &config::InputBlock) {
+    fn load_block(
+        &mut self,
+        inputs: &config::InputBlock,
+        context: &InterpolationContext<'_>,
+    ) -> Result<(), InvalidInputPath> {
         for path in &inputs.paths {
-            self.paths.push(path.clone().into_inner());
+            let glob = Glob::new(&path.render(context)).map_err(|error| InvalidInputPath {
+                span: path.span,
+                error,
+            })?;
+            self.paths.push(glob);
         }
 
-        for _var in &inputs.env_vars {
-            self.env_vars.push(_var.to_owned());
-            todo!("Haven't implemented env var input support yet");
+        for var in &inputs.env_vars {
+            self.env_vars.push(var.render(context));
         }
 
         for _command in &inputs.commands {
-            self.commands.push(_command.to_owned());
+            self.commands.push(_command.render(context));
             todo!("Haven't implemented command input support yet");
         }
+
+        Ok(())
     }
 }
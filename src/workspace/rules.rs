@@ -0,0 +1,199 @@
+//! Rules from the workspace file restricting which projects can depend on each other
+
+use std::collections::HashMap;
+
+use globset::GlobMatcher;
+use miette::SourceSpan;
+
+use super::{ProjectInfo, ProjectRef, Workspace};
+use crate::{
+    config::{self, ConfigSource},
+    diagnostics::DynDiagnostic,
+};
+
+pub(super) struct DependencyRules {
+    rules: Vec<DependencyRule>,
+    /// The workspace file the rules were defined in
+    source: ConfigSource,
+}
+
+struct DependencyRule {
+    name: String,
+    span: SourceSpan,
+    from: Vec<ProjectMatcher>,
+    allow: Vec<ProjectMatcher>,
+    deny: Vec<ProjectMatcher>,
+}
+
+struct ProjectMatcher {
+    projects: Option<GlobMatcher>,
+    tag: Option<String>,
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum RuleViolation {
+    #[error("{from} depends on {to}, which the rule {rule} denies")]
+    Denied {
+        from: String,
+        to: String,
+        rule: String,
+        #[label("this dependency breaks the rule {rule}")]
+        span: SourceSpan,
+        #[related]
+        definition: Vec<DynDiagnostic>,
+    },
+    #[error("{from} depends on {to}, which the rule {rule} doesn't allow")]
+    #[diagnostic(help("{to} doesn't match any of the projects the rule allows"))]
+    NotAllowed {
+        from: String,
+        to: String,
+        rule: String,
+        #[label("this dependency breaks the rule {rule}")]
+        span: SourceSpan,
+        #[related]
+        definition: Vec<DynDiagnostic>,
+    },
+}
+
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+#[error("{rule} is defined here")]
+struct RuleDefinedHere {
+    rule: String,
+    #[label("the rule {rule} is defined here")]
+    span: SourceSpan,
+}
+
+impl DependencyRules {
+    pub fn new(rules: Vec<config::DependencyRule>, source: ConfigSource) -> Self {
+        let matchers = |matchers: Vec<config::ProjectMatcher>| {
+            matchers
+                .into_iter()
+                .map(|matcher| ProjectMatcher {
+                    projects: matcher
+                        .projects
+                        .map(|glob| glob.into_inner().compile_matcher()),
+                    tag: matcher.tag,
+                })
+                .collect()
+        };
+
+        DependencyRules {
+            rules: rules
+                .into_iter()
+                .map(|rule| DependencyRule {
+                    span: rule.name.span,
+                    name: rule.name.into_inner(),
+                    from: matchers(rule.from),
+                    allow: matchers(rule.allow),
+                    deny: matchers(rule.deny),
+                })
+                .collect(),
+            source,
+        }
+    }
+
+    /// Checks every projects dependencies against the rules.
+    ///
+    /// `dependency_spans` holds where each dependency was declared, which is
+    /// where any violations are reported.
+    pub fn check(
+        &self,
+        workspace: &Workspace,
+        dependency_spans: &HashMap<(ProjectRef, ProjectRef), (SourceSpan, ConfigSource)>,
+    ) -> Vec<DynDiagnostic> {
+        let mut projects = workspace.projects().collect::<Vec<_>>();
+        projects.sort_by_key(|project| project.project_ref());
+
+        let mut violations = Vec::new();
+        for project in projects {
+            for (dependency, _) in &project.dependencies {
+                let dependency = dependency.lookup(workspace);
+                for rule in &self.rules {
+                    let Some(breach) = rule.check(project, dependency) else {
+                        continue;
+                    };
+                    let (span, source) =
+                        &dependency_spans[&(project.project_ref(), dependency.project_ref())];
+                    let violation =
+                        rule.violation(breach, project, dependency, *span, &self.source);
+                    violations.push(DynDiagnostic::new(violation).with_source_code(source.clone()));
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// How a dependency breaks a rule
+enum Breach {
+    Denied,
+    NotAllowed,
+}
+
+impl DependencyRule {
+    fn check(&self, project: &ProjectInfo, dependency: &ProjectInfo) -> Option<Breach> {
+        if !self.from.is_empty() && !self.from.iter().any(|m| m.matches(project)) {
+            return None;
+        }
+
+        if self.deny.iter().any(|m| m.matches(dependency)) {
+            return Some(Breach::Denied);
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|m| m.matches(dependency)) {
+            return Some(Breach::NotAllowed);
+        }
+        None
+    }
+
+    fn violation(
+        &self,
+        breach: Breach,
+        project: &ProjectInfo,
+        dependency: &ProjectInfo,
+        span: SourceSpan,
+        source: &ConfigSource,
+    ) -> RuleViolation {
+        let from = project.name.clone();
+        let to = dependency.name.clone();
+        let rule = self.name.clone();
+        let definition = vec![DynDiagnostic::new(RuleDefinedHere {
+            rule: rule.clone(),
+            span: self.span,
+        })
+        .with_source_code(source.clone())];
+
+        match breach {
+            Breach::Denied => RuleViolation::Denied {
+                from,
+                to,
+                rule,
+                span,
+                definition,
+            },
+            Breach::NotAllowed => RuleViolation::NotAllowed {
+                from,
+                to,
+                rule,
+                span,
+                definition,
+            },
+        }
+    }
+}
+
+impl ProjectMatcher {
+    fn matches(&self, project: &ProjectInfo) -> bool {
+        let path_matches = self
+            .projects
+            .as_ref()
+            .map(|glob| glob.is_match(project.root.as_subpath()))
+            .unwrap_or(true);
+        let tag_matches = self
+            .tag
+            .as_ref()
+            .map(|tag| project.tags.contains(tag))
+            .unwrap_or(true);
+
+        path_matches && tag_matches
+    }
+}
@@ -38,10 +38,202 @@ fn test_task_ref_direct_dependencies() {
     )
 }
 
-fn a_workspace() -> Workspace {
+/// Loads the sample workspace in src/workspace/test-data
+pub(crate) fn a_workspace() -> Workspace {
     let config = load_config_from_path("src/workspace/test-data/".into()).unwrap();
 
     let mut workspace = Workspace::new(config.workspace_file);
     workspace.add_projects(config.project_files).unwrap();
     workspace
 }
+
+#[test]
+fn test_env_passthrough_globset() {
+    let workspace = a_workspace();
+    let globset = workspace.env_passthrough_globset();
+
+    assert!(globset.is_match("PATH"));
+    assert!(globset.is_match("CARGO_HOME"));
+    assert!(!globset.is_match("HOME"));
+}
+
+#[test]
+fn test_root_tasks() {
+    let workspace = a_workspace();
+
+    let release = workspace.root_task("release").unwrap();
+    assert_eq!(release.task_ref().to_string(), "//::release");
+    assert_eq!(release.cwd, ValidPath::from(workspace.root_path().clone()));
+
+    let build_service_ref = workspace
+        .project_at_path("projects/a-service")
+        .unwrap()
+        .lookup_task("build", &workspace)
+        .unwrap()
+        .task_ref();
+
+    assert_eq!(
+        release.task_ref().direct_dependencies(&workspace),
+        maplit::hashset! { build_service_ref }
+    );
+    assert!(workspace
+        .projects()
+        .all(|project| project.lookup_task("release", &workspace).is_none()));
+}
+
+#[test]
+fn test_optional_requires_skip_missing_tasks() {
+    let workspace = a_workspace();
+    let service = workspace.project_at_path("projects/a-service").unwrap();
+
+    let build_service_ref = service.lookup_task("build", &workspace).unwrap().task_ref();
+    let test_service_ref = service.lookup_task("test", &workspace).unwrap().task_ref();
+
+    assert_eq!(
+        test_service_ref.direct_dependencies(&workspace),
+        maplit::hashset! { build_service_ref }
+    );
+}
+
+#[test]
+fn test_requires_selectors() {
+    let workspace = a_workspace();
+    let lib = workspace.project_at_path("projects/a-lib").unwrap();
+    let service = workspace.project_at_path("projects/a-service").unwrap();
+
+    let task_ref = |project: &ProjectInfo, name: &str| {
+        project.lookup_task(name, &workspace).unwrap().task_ref()
+    };
+
+    assert_eq!(
+        task_ref(lib, "check-consumers").direct_dependencies(&workspace),
+        maplit::hashset! { task_ref(service, "test") }
+    );
+    // Dev dependencies are followed unless a requires has include_dev=false
+    let fixtures = workspace.project_at_path("projects/test-fixtures").unwrap();
+    assert_eq!(
+        task_ref(service, "package").direct_dependencies(&workspace),
+        maplit::hashset! { task_ref(lib, "build"), task_ref(fixtures, "build") }
+    );
+}
+
+#[test]
+fn test_dev_dependencies() {
+    let workspace = a_workspace();
+    let lib = workspace.project_at_path("projects/a-lib").unwrap();
+    let service = workspace.project_at_path("projects/a-service").unwrap();
+    let fixtures = workspace.project_at_path("projects/test-fixtures").unwrap();
+
+    assert_eq!(
+        service.direct_dependencies::<HashSet<_>>(&workspace, DevDependencies::Include),
+        maplit::hashset! { lib.project_ref(), fixtures.project_ref() }
+    );
+    assert_eq!(
+        service.direct_dependencies::<HashSet<_>>(&workspace, DevDependencies::Exclude),
+        maplit::hashset! { lib.project_ref() }
+    );
+
+    let graph = workspace.graph();
+    assert_eq!(
+        graph.walk_project_dependents(fixtures.project_ref(), DevDependencies::Include),
+        maplit::hashset! { fixtures.project_ref(), service.project_ref() }
+    );
+    assert_eq!(
+        graph.walk_project_dependents(fixtures.project_ref(), DevDependencies::Exclude),
+        maplit::hashset! { fixtures.project_ref() }
+    );
+}
+
+#[test]
+fn test_conditions() {
+    let workspace = a_workspace();
+    let fixtures = workspace.project_at_path("projects/test-fixtures").unwrap();
+
+    let build = fixtures.lookup_task("build", &workspace).unwrap();
+    assert_eq!(build.commands, vec![r#"echo "build-test-fixtures""#]);
+    assert!(build.inputs.is_empty());
+    assert_eq!(build.disabled, None);
+    assert!(build.task_ref().direct_dependencies(&workspace).is_empty());
+
+    let seed = fixtures.lookup_task("seed", &workspace).unwrap();
+    assert_eq!(
+        seed.disabled.as_deref(),
+        Some("NABS_TEST_NEVER_SET isn't set")
+    );
+    assert!(seed.task_ref().direct_dependencies(&workspace).is_empty());
+}
+
+#[test]
+fn test_matrix_tasks() {
+    let workspace = a_workspace();
+    let fixtures = workspace.project_at_path("projects/test-fixtures").unwrap();
+    let task_ref = |name: &str| fixtures.lookup_task(name, &workspace).unwrap().task_ref();
+
+    let compile = fixtures
+        .tasks(&workspace)
+        .into_iter()
+        .filter(|task| task.answers_to("compile"))
+        .map(|task| task.name.as_str())
+        .collect::<HashSet<_>>();
+    assert_eq!(
+        compile,
+        maplit::hashset! {
+            "compile[target=x86_64-unknown-linux-gnu][profile=dev]",
+            "compile[target=x86_64-unknown-linux-gnu][profile=release]",
+            "compile[target=wasm32-unknown-unknown][profile=dev]",
+            "compile[target=wasm32-unknown-unknown][profile=release]",
+        }
+    );
+
+    let wasm_release = fixtures
+        .lookup_task(
+            "compile[target=wasm32-unknown-unknown][profile=release]",
+            &workspace,
+        )
+        .unwrap();
+    assert_eq!(wasm_release.base_name(), "compile");
+    assert_eq!(
+        wasm_release.commands,
+        vec!["cargo build --target wasm32-unknown-unknown --profile release"]
+    );
+
+    // Instances only require the instances that share their matrix values
+    assert_eq!(
+        task_ref("package[target=wasm32-unknown-unknown]").direct_dependencies(&workspace),
+        maplit::hashset! {
+            task_ref("compile[target=wasm32-unknown-unknown][profile=dev]"),
+            task_ref("compile[target=wasm32-unknown-unknown][profile=release]"),
+        }
+    );
+}
+
+#[test]
+fn test_alias_tasks() {
+    let workspace = a_workspace();
+    let fixtures = workspace.project_at_path("projects/test-fixtures").unwrap();
+
+    let ci = fixtures.lookup_task("ci", &workspace).unwrap();
+    assert!(ci.alias);
+    assert_eq!(ci.task_ref().direct_dependencies(&workspace).len(), 3);
+
+    assert!(!fixtures.lookup_task("build", &workspace).unwrap().alias);
+
+    // Commands filtered out by `when` don't turn a task into an alias
+    let deploy = fixtures.lookup_task("deploy", &workspace).unwrap();
+    assert!(!deploy.alias);
+    assert!(deploy.commands.is_empty());
+}
+
+#[test]
+fn test_task_descriptions_and_sources() {
+    let workspace = a_workspace();
+    let lib = workspace.project_at_path("projects/a-lib").unwrap();
+
+    let build = lib.lookup_task("build", &workspace).unwrap();
+    assert_eq!(build.description.as_deref(), Some("Builds the library"));
+    assert_eq!(build.defined_in, "projects/a-lib/project.kdl");
+
+    let lint = lib.lookup_task("lint", &workspace).unwrap();
+    assert_eq!(lint.description, None);
+    assert_eq!(lint.defined_in, "workspace.kdl");
+}
@@ -26,6 +26,66 @@ fn missing_project_file() {
     test_failing_config("missing_project_file");
 }
 
+#[test]
+fn unknown_interpolation_variable() {
+    test_failing_config("unknown_interpolation_variable");
+}
+
+#[test]
+fn template_undeclared_variable() {
+    test_failing_config("template_undeclared_variable");
+}
+
+#[test]
+fn template_use_errors() {
+    test_failing_config("template_use_errors");
+}
+
+#[test]
+fn targeted_template_with_variables() {
+    test_failing_config("targeted_template_with_variables");
+}
+
+#[test]
+fn invalid_names() {
+    test_failing_config("invalid_names");
+}
+
+#[test]
+fn duplicate_project_names() {
+    test_failing_config("duplicate_project_names");
+}
+
+#[test]
+fn project_dependency_cycle() {
+    test_failing_config("project_dependency_cycle");
+}
+
+#[test]
+fn task_requires_cycle() {
+    test_failing_config("task_requires_cycle");
+}
+
+#[test]
+fn dependency_rule_violations() {
+    test_failing_config("dependency_rule_violations");
+}
+
+#[test]
+fn empty_condition() {
+    test_failing_config("empty_condition");
+}
+
+#[test]
+fn matrix_errors() {
+    test_failing_config("matrix_errors");
+}
+
+#[test]
+fn empty_rule_matcher() {
+    test_failing_config("empty_rule_matcher");
+}
+
 fn test_failing_config(name: &str) {
     let mut cmd = Command::cargo_bin("unknown").unwrap();
     cmd.arg("projects");